@@ -0,0 +1,165 @@
+use crate::oauth::{AuthError, Claims, JwtValidation};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::RwLock;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub(crate) struct Config {
+    pub issuer_url: String,
+    pub client_id: String,
+    pub client_secret: String,
+    pub introspection_endpoint: String,
+    /// Duration to cache active tokens
+    pub cache_ttl: Duration,
+    pub validation: JwtValidation,
+}
+
+#[derive(Deserialize)]
+struct IntrospectionResponse {
+    active: bool,
+    #[serde(flatten)]
+    claims: Map<String, Value>,
+}
+
+/// Validates (opaque) tokens at the introspection endpoint of the issuer (RFC 7662).
+/// Only active tokens are cached, so revoked tokens are rejected after the cache ttl at the latest.
+pub(crate) struct TokenIntrospector {
+    config: Config,
+    client: reqwest::Client,
+    /// Claims of active tokens by token hash
+    cache: RwLock<HashMap<String, (Instant, Claims)>>,
+}
+
+impl TokenIntrospector {
+    pub fn new(config: Config) -> Result<Self, AuthError> {
+        let client = reqwest::Client::builder()
+            .connect_timeout(CONNECT_TIMEOUT)
+            .timeout(REQUEST_TIMEOUT)
+            .build()?;
+
+        Ok(Self {
+            config,
+            client,
+            cache: RwLock::new(HashMap::new()),
+        })
+    }
+
+    pub async fn validate(&self, token: &str) -> Result<Claims, AuthError> {
+        let key = format!("{:x}", Sha256::digest(token));
+
+        // Check cache first
+        {
+            let cache = self.cache.read().await;
+            if let Some((expires, claims)) = cache.get(&key)
+                && *expires > Instant::now()
+            {
+                log::debug!("Token introspection cache hit");
+                return Ok(claims.clone());
+            }
+        }
+
+        let claims = self.introspect(token).await?;
+
+        // cache until the token expires at the latest
+        let expires = Instant::now() + self.config.cache_ttl.min(expires_in(&claims));
+        let mut cache = self.cache.write().await;
+        cache.retain(|_, (e, _)| *e > Instant::now());
+        cache.insert(key, (expires, claims.clone()));
+
+        Ok(claims)
+    }
+
+    async fn introspect(&self, token: &str) -> Result<Claims, AuthError> {
+        log::debug!(
+            "Introspecting token at: {}",
+            self.config.introspection_endpoint
+        );
+
+        let response = self
+            .client
+            .post(&self.config.introspection_endpoint)
+            .basic_auth(&self.config.client_id, Some(&self.config.client_secret))
+            .form(&[("token", token), ("token_type_hint", "access_token")])
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(AuthError::Client(format!(
+                "Token introspection request failed with status: {}",
+                response.status()
+            )));
+        }
+
+        let introspection: IntrospectionResponse = response.json().await.map_err(|e| {
+            AuthError::Client(format!("Failed to parse introspection response: {e}"))
+        })?;
+        if !introspection.active {
+            return Err(AuthError::Inactive);
+        }
+        self.check(&introspection.claims)?;
+
+        serde_json::from_value(Value::Object(introspection.claims))
+            .map_err(|e| AuthError::Client(format!("Failed to parse claims of active token: {e}")))
+    }
+
+    /// Active tokens of the realm may be issued to other clients or be refresh tokens, so the
+    /// claims are checked like the claims of a JWT
+    fn check(&self, claims: &Map<String, Value>) -> Result<(), AuthError> {
+        let validation = &self.config.validation;
+
+        // access tokens only (`typ` is set by Keycloak)
+        for claim in ["token_type", "typ"] {
+            if let Some(token_type) = claims.get(claim).and_then(Value::as_str)
+                && !token_type.eq_ignore_ascii_case("bearer")
+                && !token_type.eq_ignore_ascii_case("access_token")
+            {
+                return Err(AuthError::Rejected(format!(
+                    "Unexpected token type: {token_type}"
+                )));
+            }
+        }
+
+        if let Some(iss) = claims.get("iss").and_then(Value::as_str)
+            && iss != self.config.issuer_url
+        {
+            return Err(AuthError::Rejected(format!("Unexpected issuer: {iss}")));
+        }
+
+        if let Some(aud) = claims.get("aud") {
+            let audiences = validation.audiences(&self.config.client_id);
+            let accepted = match aud {
+                Value::String(aud) => audiences.contains(&aud.as_str()),
+                Value::Array(aud) => aud
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .any(|a| audiences.contains(&a)),
+                _ => false,
+            };
+            if !accepted {
+                return Err(AuthError::Rejected("InvalidAudience".to_string()));
+            }
+        }
+
+        if let Some(claim) = validation.missing_claim(claims) {
+            return Err(AuthError::Rejected(format!(
+                "Missing required claim: {claim}"
+            )));
+        }
+
+        if !validation.is_authorized_party(claims) {
+            return Err(AuthError::Rejected("Unauthorized party".to_string()));
+        }
+
+        Ok(())
+    }
+}
+
+fn expires_in(claims: &Claims) -> Duration {
+    let exp = UNIX_EPOCH + Duration::from_secs(claims.exp as u64);
+    exp.duration_since(SystemTime::now()).unwrap_or_default()
+}
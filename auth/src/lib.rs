@@ -1,2 +1,3 @@
+mod introspection;
 pub mod oauth;
 mod validator;
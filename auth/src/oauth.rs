@@ -1,18 +1,25 @@
+use crate::introspection::{self, TokenIntrospector};
 use crate::oauth::AuthError::Client;
 use crate::validator::{Config, TokenValidator};
-use axum::extract::{Request, State};
+use axum::extract::{FromRequestParts, OptionalFromRequestParts, Request, State};
 use axum::middleware::Next;
-use axum::response::IntoResponse;
+use axum::response::{IntoResponse, Response};
 use axum_extra::headers::authorization::Bearer;
 use axum_extra::headers::Authorization;
 use axum_extra::TypedHeader;
-use http::StatusCode;
+use http::request::Parts;
+use http::{header, StatusCode};
 use log::{debug, error};
 use oauth2::basic::{BasicClient, BasicRequestTokenError};
 use oauth2::url::ParseError;
 use oauth2::{EndpointNotSet, EndpointSet};
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::fmt;
 use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Debug, thiserror::Error)]
 pub enum AuthError {
@@ -30,25 +37,208 @@ pub enum AuthError {
 
     #[error("OIDC client error: {0}")]
     Client(String),
+
+    #[error("Token is not active")]
+    Inactive,
+
+    #[error("Token rejected: {0}")]
+    Rejected(String),
 }
 
 pub type BasicClientSet =
     BasicClient<EndpointSet, EndpointNotSet, EndpointNotSet, EndpointNotSet, EndpointSet>;
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct Claims {
     pub sub: String,
     pub exp: usize,
-    pub iat: usize,
-    pub iss: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iat: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    /// Authorized party, the client the token was issued to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub azp: Option<String>,
+    /// Client of the token (RFC 7662 introspection)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    /// Client of a Keycloak service account token
+    #[serde(default, rename = "clientId", skip_serializing_if = "Option::is_none")]
+    pub service_account_client_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preferred_username: Option<String>,
+    /// Keycloak realm roles
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub realm_access: Option<Access>,
+    /// Keycloak client roles by client id
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub resource_access: HashMap<String, Access>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct Access {
+    #[serde(default)]
+    pub roles: Vec<String>,
+}
+
+impl Claims {
+    /// Check if the space separated `scope` claim contains the given scope
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scope
+            .as_deref()
+            .is_some_and(|s| s.split(' ').any(|s| s == scope))
+    }
+
+    /// Check if the realm roles contain the given role
+    pub fn has_role(&self, role: &str) -> bool {
+        self.realm_access
+            .as_ref()
+            .is_some_and(|a| a.roles.iter().any(|r| r == role))
+    }
+
+    /// Check if the roles of a client contain the given role
+    pub fn has_client_role(&self, client: &str, role: &str) -> bool {
+        self.resource_access
+            .get(client)
+            .is_some_and(|a| a.roles.iter().any(|r| r == role))
+    }
+
+    /// Client the token was issued to
+    pub fn client(&self) -> Option<&str> {
+        self.client_id
+            .as_deref()
+            .or(self.service_account_client_id.as_deref())
+            .or(self.azp.as_deref())
+    }
+
+    /// Readable name of the principal, the user name or the client
+    pub fn name(&self) -> Option<&str> {
+        self.preferred_username.as_deref().or(self.client())
+    }
+
+    /// Check if a grant is given as scope, realm role or client role (`client:role`)
+    pub fn is_granted(&self, grant: &str) -> bool {
+        match grant.split_once(':') {
+            Some((client, role)) => self.has_client_role(client, role),
+            None => self.has_scope(grant) || self.has_role(grant),
+        }
+    }
+}
+
+/// Principal for logging, e.g. `alice (client: portal, sub: 1234)`
+impl fmt::Display for Claims {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.preferred_username, self.client()) {
+            (Some(user), Some(client)) => {
+                write!(f, "{user} (client: {client}, sub: {})", self.sub)
+            }
+            (Some(user), None) => write!(f, "{user} (sub: {})", self.sub),
+            (None, Some(client)) => write!(f, "client {client} (sub: {})", self.sub),
+            (None, None) => write!(f, "sub {}", self.sub),
+        }
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Claims {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<Claims>()
+            .cloned()
+            .ok_or_else(|| unauthorized("Bearer token missing"))
+    }
+}
+
+/// Claims are not available if authentication is disabled
+impl<S: Send + Sync> OptionalFromRequestParts<S> for Claims {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &S,
+    ) -> Result<Option<Self>, Self::Rejection> {
+        Ok(parts.extensions.get::<Claims>().cloned())
+    }
+}
+
+/// Validation of access token claims in addition to signature, issuer and expiry.
+/// The leeway only applies to JWT validation.
+#[derive(Debug, Clone)]
+pub struct JwtValidation {
+    /// Accepted audiences. Defaults to the client id
+    pub audiences: Vec<String>,
+    /// Seconds of clock skew allowed for `exp` and `nbf`
+    pub leeway: u64,
+    /// Claims which must be present
+    pub required_claims: Vec<String>,
+    /// Accepted authorized parties (`azp`). Not checked if empty
+    pub authorized_parties: Vec<String>,
+}
+
+impl Default for JwtValidation {
+    fn default() -> Self {
+        JwtValidation {
+            audiences: vec![],
+            leeway: 60,
+            required_claims: vec![],
+            authorized_parties: vec![],
+        }
+    }
+}
+
+impl JwtValidation {
+    /// Accepted audiences, the client id by default
+    pub(crate) fn audiences<'a>(&'a self, client_id: &'a str) -> Vec<&'a str> {
+        if self.audiences.is_empty() {
+            vec![client_id]
+        } else {
+            self.audiences.iter().map(String::as_str).collect()
+        }
+    }
+
+    /// First required claim which is missing
+    pub(crate) fn missing_claim(&self, claims: &Map<String, Value>) -> Option<&str> {
+        self.required_claims
+            .iter()
+            .map(String::as_str)
+            .find(|c| !claims.contains_key(*c))
+    }
+
+    /// Checks the authorized party (`azp`) if authorized parties are configured
+    pub(crate) fn is_authorized_party(&self, claims: &Map<String, Value>) -> bool {
+        if self.authorized_parties.is_empty() {
+            return true;
+        }
+        let azp = claims.get("azp").and_then(Value::as_str);
+        azp.is_some_and(|azp| self.authorized_parties.iter().any(|p| p == azp))
+    }
+}
+
+enum Validator {
+    /// Local validation of JWT access tokens
+    Jwt(TokenValidator),
+    /// Token introspection at the issuer (RFC 7662)
+    Introspection(TokenIntrospector),
 }
 
 pub struct Oidc {
-    validator: TokenValidator,
+    validator: Validator,
 }
 
 impl Oidc {
     pub async fn new(client_id: String, issuer_url: String) -> Result<Oidc, AuthError> {
+        Self::with_jwt_validation(client_id, issuer_url, JwtValidation::default()).await
+    }
+
+    pub async fn with_jwt_validation(
+        client_id: String,
+        issuer_url: String,
+        validation: JwtValidation,
+    ) -> Result<Oidc, AuthError> {
         let discovery: DiscoveryDocument = DiscoveryDocument::new(&issuer_url).await?;
 
         // jwt validation config
@@ -56,21 +246,54 @@ impl Oidc {
             issuer_url,
             client_id,
             jwks_uri: discovery.jwks_uri,
+            validation,
         };
-        let validator = TokenValidator::new(config);
+        let validator = Validator::Jwt(TokenValidator::new(config));
 
         Ok(Oidc { validator })
     }
 
-    pub(crate) async fn authenticate(&self, token: &str) -> Result<(), AuthError> {
-        match self.validator.validate::<Claims>(token).await {
+    /// Validates tokens by introspection, which also accepts opaque tokens and rejects revoked
+    /// tokens. Active tokens are cached for `cache_ttl`.
+    pub async fn with_introspection(
+        client_id: String,
+        client_secret: String,
+        issuer_url: String,
+        cache_ttl: Duration,
+        validation: JwtValidation,
+    ) -> Result<Oidc, AuthError> {
+        let discovery: DiscoveryDocument = DiscoveryDocument::new(&issuer_url).await?;
+
+        let config = introspection::Config {
+            issuer_url,
+            client_id,
+            client_secret,
+            introspection_endpoint: discovery.introspection_endpoint,
+            cache_ttl,
+            validation,
+        };
+        let validator = Validator::Introspection(TokenIntrospector::new(config)?);
+
+        Ok(Oidc { validator })
+    }
+
+    pub(crate) async fn authenticate(&self, token: &str) -> Result<Claims, AuthError> {
+        let result = match &self.validator {
+            Validator::Jwt(validator) => validator
+                .validate::<Claims>(token)
+                .await
+                .map_err(AuthError::JWT),
+            Validator::Introspection(introspector) => introspector.validate(token).await,
+        };
+
+        match result {
             Ok(claims) => {
-                debug!("Valid token for sub: {}", claims.sub);
-                Ok(())
+                debug!("Valid token for {claims}");
+                Ok(claims)
             }
             Err(e) => {
                 error!("Bearer token validation failed: {e}");
-                Err(AuthError::JWT(e))
+                Err(e)
             }
         }
     }
@@ -79,19 +302,65 @@ impl Oidc {
 pub async fn auth_middleware(
     State(state): State<Arc<Oidc>>,
     creds: Option<TypedHeader<Authorization<Bearer>>>,
-    request: Request,
+    mut request: Request,
     next: Next,
 ) -> impl IntoResponse {
     if let Some(c) = creds {
         match state.authenticate(c.token()).await {
-            Ok(_) => next.run(request).await,
-            Err(e) => (StatusCode::UNAUTHORIZED, e.to_string()).into_response(),
+            Ok(claims) => {
+                // provide claims to subsequent layers and handlers
+                request.extensions_mut().insert(claims);
+                next.run(request).await
+            }
+            Err(e) => unauthorized(&e.to_string()),
         }
     } else {
-        (StatusCode::UNAUTHORIZED, "Bearer token missing").into_response()
+        unauthorized("Bearer token missing")
+    }
+}
+
+/// Requires the given scope or role (see [`Claims::is_granted`]) in the claims of an
+/// authenticated request. Must be applied inside of [`auth_middleware`].
+pub async fn scope_middleware(
+    State(scope): State<String>,
+    request: Request,
+    next: Next,
+) -> impl IntoResponse {
+    match request.extensions().get::<Claims>() {
+        Some(claims) if claims.is_granted(&scope) => next.run(request).await,
+        Some(claims) => {
+            debug!("Missing scope '{scope}' for sub: {}", claims.sub);
+            problem(
+                StatusCode::FORBIDDEN,
+                "forbidden",
+                &format!("Missing scope: {scope}"),
+            )
+        }
+        None => unauthorized("Bearer token missing"),
     }
 }
 
+/// Problem details (RFC 9457) of a rejected request, in the shape of the API errors
+fn problem(status: StatusCode, code: &str, detail: &str) -> Response {
+    let body = json!({
+        "type": format!("urn:ttp-idm:error:{code}"),
+        "title": status.canonical_reason().unwrap_or_default(),
+        "status": status.as_u16(),
+        "detail": detail,
+        "code": code,
+    });
+    (
+        status,
+        [(header::CONTENT_TYPE, "application/problem+json")],
+        body.to_string(),
+    )
+        .into_response()
+}
+
+fn unauthorized(detail: &str) -> Response {
+    problem(StatusCode::UNAUTHORIZED, "unauthorized", detail)
+}
+
 #[derive(Deserialize)]
 pub struct DiscoveryDocument {
     pub issuer: String,
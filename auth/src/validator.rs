@@ -1,15 +1,22 @@
+use crate::oauth::JwtValidation;
 use jsonwebtoken::errors::{Error as JwtError, ErrorKind, Result as JwtResult};
 use jsonwebtoken::jwk::{Jwk, JwkSet};
 use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
 use serde::Deserialize;
+use serde_json::{Map, Value};
 use std::collections::HashMap;
+use std::str::FromStr;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
+/// Registered claims which are validated by [`jsonwebtoken`]
+const SPEC_CLAIMS: [&str; 5] = ["exp", "nbf", "aud", "iss", "sub"];
+
 pub(crate) struct Config {
     pub issuer_url: String,
     pub client_id: String,
     pub jwks_uri: String,
+    pub validation: JwtValidation,
 }
 
 pub struct TokenValidator {
@@ -105,16 +112,50 @@ impl TokenValidator {
     where
         T: for<'de> Deserialize<'de>,
     {
-        log::debug!("Validating JWT token with minimal validation");
+        log::debug!("Validating JWT token with configured validation");
+        let config = &self.config.validation;
 
-        // Create a minimal validation configuration
-        let mut validation = Validation::new(Algorithm::RS256);
+        // Signing algorithm of the key
+        let header = jsonwebtoken::decode_header(token)?;
+        let kid = header
+            .kid
+            .ok_or_else(|| JwtError::from(ErrorKind::InvalidToken))?;
+        let jwk = self.get_jwk(&kid).await?;
+        let mut validation = Validation::new(algorithm(&jwk, header.alg)?);
 
         validation.set_issuer(&[&self.config.issuer_url]);
 
-        validation.set_audience(&[&self.config.client_id]);
+        validation.set_audience(&config.audiences(&self.config.client_id));
+
+        validation.leeway = config.leeway;
+        let mut spec_claims = vec!["exp"];
+        spec_claims.extend(
+            config
+                .required_claims
+                .iter()
+                .map(String::as_str)
+                .filter(|c| SPEC_CLAIMS.contains(c)),
+        );
+        validation.set_required_spec_claims(&spec_claims);
+
+        let claims = self
+            .validate_custom::<Map<String, Value>>(token, &validation)
+            .await?;
+
+        // Other required claims
+        if let Some(claim) = config.missing_claim(&claims) {
+            return Err(JwtError::from(ErrorKind::MissingRequiredClaim(
+                claim.to_string(),
+            )));
+        }
 
-        self.validate_custom(token, &validation).await
+        if !config.is_authorized_party(&claims) {
+            log::debug!("Token azp not authorized: {:?}", claims.get("azp"));
+            return Err(JwtError::from(ErrorKind::InvalidToken));
+        }
+
+        serde_json::from_value(Value::Object(claims))
+            .map_err(|e| JwtError::from(ErrorKind::Json(Arc::new(e))))
     }
 
     /// Refreshes the JWKS cache by fetching the latest keys
@@ -170,3 +211,17 @@ impl TokenValidator {
         Ok(())
     }
 }
+
+/// Algorithm of the key. Keys without `alg` accept the asymmetric algorithm of the token header
+/// if it matches the key type.
+fn algorithm(jwk: &Jwk, header_alg: Algorithm) -> JwtResult<Algorithm> {
+    match jwk.common.key_algorithm {
+        Some(alg) => Algorithm::from_str(&alg.to_string()),
+        None => match header_alg {
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => {
+                Err(JwtError::from(ErrorKind::InvalidAlgorithm))
+            }
+            alg => Ok(alg),
+        },
+    }
+}
@@ -0,0 +1,219 @@
+use crate::config::{Oidc, TrialAccess};
+use crate::error::ApiError;
+use auth::oauth::Claims;
+use std::collections::HashMap;
+
+/// Access of a principal to a trial
+#[derive(Debug, PartialEq)]
+pub(crate) enum TrialGrant {
+    /// Trial and all its labs
+    All,
+    /// Only the given labs of the trial
+    Labs(Vec<String>),
+}
+
+impl TrialGrant {
+    pub(crate) fn allows_lab(&self, lab: &str) -> bool {
+        match self {
+            TrialGrant::All => true,
+            TrialGrant::Labs(labs) => labs.iter().any(|l| l == lab),
+        }
+    }
+}
+
+/// Configured authorization rules for trials and match resolution.
+/// Requests without claims (authentication disabled) are not restricted.
+#[derive(Clone, Default)]
+pub(crate) struct AccessRules {
+    steward_role: Option<String>,
+    trials: HashMap<String, TrialAccess>,
+    deny_by_default: bool,
+}
+
+impl AccessRules {
+    pub(crate) fn new(config: &Oidc) -> Self {
+        AccessRules {
+            steward_role: config.steward_role.clone(),
+            trials: config.access.clone(),
+            deny_by_default: config.deny_by_default,
+        }
+    }
+
+    /// Access of the principal to a trial. Trials without rules are not restricted, unless
+    /// `deny_by_default` is set
+    pub(crate) fn trial_grant(
+        &self,
+        claims: Option<&Claims>,
+        trial: &str,
+    ) -> Result<TrialGrant, ApiError> {
+        let Some(claims) = claims else {
+            return Ok(TrialGrant::All);
+        };
+        let Some(access) = self.trials.get(trial) else {
+            return if self.deny_by_default {
+                Err(ApiError::Forbidden(format!("No access to trial {trial}")))
+            } else {
+                Ok(TrialGrant::All)
+            };
+        };
+        if access.grants.iter().any(|g| claims.is_granted(g)) {
+            return Ok(TrialGrant::All);
+        }
+
+        let mut labs = access
+            .labs
+            .iter()
+            .filter(|(_, grants)| grants.iter().any(|g| claims.is_granted(g)))
+            .map(|(lab, _)| lab.clone())
+            .collect::<Vec<_>>();
+        if labs.is_empty() {
+            return Err(ApiError::Forbidden(format!("No access to trial {trial}")));
+        }
+        labs.sort();
+
+        Ok(TrialGrant::Labs(labs))
+    }
+
+    /// Requires access to the whole trial
+    pub(crate) fn check_trial(&self, claims: Option<&Claims>, trial: &str) -> Result<(), ApiError> {
+        match self.trial_grant(claims, trial)? {
+            TrialGrant::All => Ok(()),
+            TrialGrant::Labs(_) => Err(ApiError::Forbidden(format!("No access to trial {trial}"))),
+        }
+    }
+
+    /// Requires access to all given labs of the trial, or to the whole trial without labs
+    pub(crate) fn check_labs<'a>(
+        &self,
+        claims: Option<&Claims>,
+        trial: &str,
+        labs: impl Iterator<Item = &'a String>,
+    ) -> Result<(), ApiError> {
+        let mut labs = labs.peekable();
+        if labs.peek().is_none() {
+            return self.check_trial(claims, trial);
+        }
+
+        let grant = self.trial_grant(claims, trial)?;
+        match labs.find(|l| !grant.allows_lab(l)) {
+            Some(lab) => Err(ApiError::Forbidden(format!(
+                "No access to lab {lab} of trial {trial}"
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Requires the steward role to resolve matches
+    pub(crate) fn check_steward(&self, claims: Option<&Claims>) -> Result<(), ApiError> {
+        match (claims, &self.steward_role) {
+            (Some(claims), Some(role)) if !claims.is_granted(role) => {
+                Err(ApiError::Forbidden(format!("Missing role: {role}")))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::access::{AccessRules, TrialGrant};
+    use crate::config::{Oidc, TrialAccess};
+    use auth::oauth::{Access, Claims};
+    use std::collections::HashMap;
+
+    fn claims(roles: &[&str]) -> Claims {
+        Claims {
+            realm_access: Some(Access {
+                roles: roles.iter().map(|r| r.to_string()).collect(),
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn setup_rules() -> AccessRules {
+        AccessRules::new(&Oidc {
+            steward_role: Some("steward".to_string()),
+            access: HashMap::from([(
+                "trial".to_string(),
+                TrialAccess {
+                    grants: vec!["trial_manager".to_string()],
+                    labs: HashMap::from([
+                        ("lab_a".to_string(), vec!["lab_a".to_string()]),
+                        ("lab_b".to_string(), vec!["lab_b".to_string()]),
+                    ]),
+                },
+            )]),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_trial_grant() {
+        let rules = setup_rules();
+
+        // full access
+        let manager = claims(&["trial_manager"]);
+        assert_eq!(
+            rules.trial_grant(Some(&manager), "trial").unwrap(),
+            TrialGrant::All
+        );
+        assert!(rules.check_trial(Some(&manager), "trial").is_ok());
+
+        // lab access
+        let lab = claims(&["lab_a"]);
+        assert_eq!(
+            rules.trial_grant(Some(&lab), "trial").unwrap(),
+            TrialGrant::Labs(vec!["lab_a".to_string()])
+        );
+        assert!(rules.check_trial(Some(&lab), "trial").is_err());
+        assert!(
+            rules
+                .check_labs(Some(&lab), "trial", ["lab_a".to_string()].iter())
+                .is_ok()
+        );
+        assert!(
+            rules
+                .check_labs(Some(&lab), "trial", ["lab_b".to_string()].iter())
+                .is_err()
+        );
+        // participants without labs require access to the trial
+        assert!(rules.check_labs(Some(&lab), "trial", [].iter()).is_err());
+        assert!(rules.check_labs(Some(&manager), "trial", [].iter()).is_ok());
+
+        // no access
+        let other = claims(&["other"]);
+        assert!(rules.trial_grant(Some(&other), "trial").is_err());
+
+        // trials without rules and disabled authentication
+        assert!(rules.check_trial(Some(&other), "other_trial").is_ok());
+        assert!(rules.check_trial(None, "trial").is_ok());
+    }
+
+    #[test]
+    fn test_deny_by_default() {
+        let rules = AccessRules {
+            deny_by_default: true,
+            ..setup_rules()
+        };
+
+        // trials without rules are denied
+        let manager = claims(&["trial_manager"]);
+        assert!(rules.check_trial(Some(&manager), "trial").is_ok());
+        assert!(rules.check_trial(Some(&manager), "other_trial").is_err());
+        assert!(rules.check_trial(None, "other_trial").is_ok());
+    }
+
+    #[test]
+    fn test_check_steward() {
+        let rules = setup_rules();
+
+        assert!(rules.check_steward(Some(&claims(&["steward"]))).is_ok());
+        assert!(rules.check_steward(Some(&claims(&["lab_a"]))).is_err());
+        assert!(rules.check_steward(None).is_ok());
+        assert!(
+            AccessRules::default()
+                .check_steward(Some(&claims(&[])))
+                .is_ok()
+        );
+    }
+}
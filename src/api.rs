@@ -1,30 +1,92 @@
-use crate::error::ApiError;
+use crate::audit::Principal;
+use crate::error::{ApiError, ProblemDetails};
+use crate::idempotency::{self, Lookup, StoredResponse};
 pub(crate) use crate::model::IdRequest;
-use crate::model::{IdMatch, IdResponse, MatchStatus, PromptResponse};
+use crate::model::{
+    AuditAction, AuditEvent, AuditLink, AuditQuery, AuditResponse, BatchResponse, BatchResult,
+    DeleteParams, IdMatch, IdResponse, Idat, IdentityAction, LabDomain, LabRequest, Link,
+    MatchStatus, OpenMatch, PromptResponse, TrialRequest, TrialResponse, TrialSummary,
+    UpdateParams,
+};
+use crate::pending::PendingMatch;
+use crate::saga::{Saga, Step};
 use crate::server::ApiContext;
-use crate::ttp::client::TtpClient;
+use crate::ttp::client::{EpixScope, TtpClient};
+use crate::ttp::epix::model::PossibleMatchResult;
+use crate::validation;
 use anyhow::anyhow;
-use axum::extract::{Path, State};
-use axum::response::IntoResponse;
-use axum::routing::{get, post};
+use auth::oauth::Claims;
+use axum::body::Bytes;
+use axum::extract::{FromRequest, Path, Query, State};
+use axum::http::header::{self, HeaderMap, HeaderValue};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post, put};
 use axum::{debug_handler, Json, Router};
 use fhir_model::r4b::resources::{
     Parameters, ParametersParameter, ParametersParameterValue, Patient, Person,
 };
+use log::{info, warn};
 use reqwest::StatusCode;
+use std::collections::HashMap;
 use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+/// Header to safely retry create requests
+const IDEMPOTENCY_KEY: &str = "Idempotency-Key";
+
+/// JSON request body, rejected as problem details
+#[derive(FromRequest)]
+#[from_request(via(Json), rejection(ApiError))]
+pub(crate) struct ApiJson<T>(T);
+
+/// Maximum number of participants processed concurrently in a batch
+const BATCH_CONCURRENCY: usize = 8;
+
+/// Maximum number of participants in a batch
+const MAX_BATCH_SIZE: usize = 1000;
+
+/// Routes for re-identification which require a dedicated scope
+pub(crate) fn idat_router() -> Router<Arc<ApiContext>> {
+    Router::new().route("/api/participants/{trial}/{psn}/idat", get(read_idat))
+}
+
+/// Routes for administration which require a dedicated scope
+pub(crate) fn admin_router() -> Router<Arc<ApiContext>> {
+    Router::new()
+        .route("/api/trials", post(create_trial))
+        .route("/api/audit", get(audit))
+}
 
 pub(crate) fn router() -> Router<Arc<ApiContext>> {
     Router::new()
         .route("/api/pseudonyms/{trial}/{psn}", get(read))
+        .route("/api/pseudonyms/{trial}/{psn}/lab", post(add_lab))
+        .route("/api/pseudonyms/{trial}/lab/{lab}/{psn}", get(read_lab))
         .route("/api/pseudonyms", post(create))
+        .route("/api/pseudonyms/batch", post(create_batch))
+        .route(
+            "/api/participants/{trial}/{psn}",
+            put(update_participant).delete(delete_participant),
+        )
+        .route("/api/matches", get(list_matches))
+        .route("/api/matches/{link_id}", post(resolve_open))
+        .route("/api/matches/pending/{token}", post(resolve_pending))
+        .route("/api/trials", get(list_trials))
+        .route("/api/trials/{trial}", get(read_trial))
 }
 
 /// Create pseudonyms for a participant
+///
+/// Possible and multiple matches are returned as `PromptResponse` for resolution.
+///
+/// Retries with the same `Idempotency-Key` and body replay the first successful response.
 #[debug_handler]
 #[utoipa::path(
     post,
-    path = "/api/pseudonyms",
+    path = "/api/pseudonyms", params(
+        ("Idempotency-Key" = Option<String>, Header, description = "Key to safely retry the request"),
+    ),
     request_body(
         content = IdRequest,
         description = "Participant data and optional match resolution",
@@ -33,7 +95,11 @@ pub(crate) fn router() -> Router<Arc<ApiContext>> {
     responses(
         (status = 200, body = IdResponse),
         (status = 409, body = PromptResponse),
-        (status = 401)
+        (status = 400, body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401),
+        (status = 403, body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, body = ProblemDetails, content_type = "application/problem+json", description = "Invalid IDAT or Idempotency-Key was used with a different body"),
+        (status = "5XX", body = ProblemDetails, content_type = "application/problem+json")
     ),
     security(
         ("oauth" = []),
@@ -41,82 +107,913 @@ pub(crate) fn router() -> Router<Arc<ApiContext>> {
 )]
 pub(crate) async fn create(
     State(ctx): State<Arc<ApiContext>>,
-    Json(payload): Json<IdRequest>,
+    claims: Option<Claims>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, ApiError> {
+    let Json(payload) = Json::<IdRequest>::from_bytes(&body)?;
+    ctx.access
+        .check_labs(claims.as_ref(), &payload.trial, payload.lab.keys())?;
+    let principal = Principal::from(&claims);
+
+    let Some(key) = headers.get(IDEMPOTENCY_KEY) else {
+        let (status, body) = create_response(&ctx, payload, &principal).await?;
+        return Ok(json_response(status, body));
+    };
+    let key = key
+        .to_str()
+        .map_err(|_| ApiError::BadRequest(format!("Invalid {IDEMPOTENCY_KEY} header")))?;
+    let key = idempotency::scoped_key(&principal, key);
+
+    match ctx
+        .idempotency
+        .begin(&key, &idempotency::hash(&body))
+        .await?
+    {
+        Lookup::New => {
+            // finish processing even if the client disconnects
+            let task = {
+                let (ctx, key) = (Arc::clone(&ctx), key.clone());
+                tokio::spawn(async move {
+                    match create_response(&ctx, payload, &principal).await {
+                        // prompts contain IDAT of other persons and are not stored
+                        Ok((StatusCode::OK, body)) => {
+                            let stored = StoredResponse {
+                                status: StatusCode::OK.as_u16(),
+                                body: body.clone(),
+                            };
+                            // the pseudonyms were created, even if the response is not stored
+                            ctx.idempotency.finish(&key, stored).await;
+                            Ok(json_response(StatusCode::OK, body))
+                        }
+                        res => {
+                            // allow retries of prompted or failed requests
+                            ctx.idempotency.abort(&key).await?;
+                            res.map(|(status, body)| json_response(status, body))
+                        }
+                    }
+                })
+            };
+
+            match task.await {
+                Ok(res) => res,
+                Err(e) => {
+                    // release the key of a panicked task
+                    ctx.idempotency.abort(&key).await?;
+                    Err(e.into())
+                }
+            }
+        }
+        Lookup::Replay(stored) => {
+            let mut response = json_response(StatusCode::from_u16(stored.status)?, stored.body);
+            response
+                .headers_mut()
+                .insert("Idempotent-Replayed", HeaderValue::from_static("true"));
+            Ok(response)
+        }
+        Lookup::InProgress => Err(ApiError::Conflict(format!(
+            "A request with this {IDEMPOTENCY_KEY} is still being processed"
+        ))),
+        Lookup::Conflict => Err(ApiError::IdempotencyKeyReused),
+    }
+}
+
+async fn create_response(
+    ctx: &ApiContext,
+    payload: IdRequest,
+    principal: &Principal,
+) -> Result<(StatusCode, String), ApiError> {
+    match create_pseudonyms(ctx, payload, principal).await? {
+        CreateResult::Created(res) => Ok((StatusCode::OK, serde_json::to_string(&res)?)),
+        CreateResult::Prompt(prompt) => Ok((StatusCode::CONFLICT, serde_json::to_string(&prompt)?)),
+    }
+}
+
+fn json_response(status: StatusCode, body: String) -> Response {
+    (status, [(header::CONTENT_TYPE, "application/json")], body).into_response()
+}
+
+/// Create pseudonyms for multiple participants
+#[debug_handler]
+#[utoipa::path(
+    post,
+    path = "/api/pseudonyms/batch",
+    request_body(
+        content = Vec<IdRequest>,
+        description = "List of participant data and optional match resolutions",
+        content_type = "application/json"
+    ),
+    responses(
+        (status = 200, body = BatchResponse),
+        (status = 401),
+        (status = 422, body = ProblemDetails, content_type = "application/problem+json", description = "Too many participants"),
+        (status = "5XX", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    security(
+        ("oauth" = []),
+    )
+)]
+pub(crate) async fn create_batch(
+    State(ctx): State<Arc<ApiContext>>,
+    claims: Option<Claims>,
+    ApiJson(payload): ApiJson<Vec<IdRequest>>,
 ) -> Result<impl IntoResponse, ApiError> {
+    if payload.len() > MAX_BATCH_SIZE {
+        return Err(ApiError::Validation(format!(
+            "Batch contains {} participants, at most {MAX_BATCH_SIZE} are allowed",
+            payload.len()
+        )));
+    }
+
+    let permits = Arc::new(Semaphore::new(BATCH_CONCURRENCY));
+
+    let mut results = Vec::new();
+    let mut set = JoinSet::new();
+    for (index, id_request) in payload.into_iter().enumerate() {
+        // forbidden participants are reported like failed ones
+        if let Err(e) =
+            ctx.access
+                .check_labs(claims.as_ref(), &id_request.trial, id_request.lab.keys())
+        {
+            results.push((index, Err(e)));
+            continue;
+        }
+
+        // spawn only as many tasks as may run concurrently
+        let permit = Arc::clone(&permits)
+            .acquire_owned()
+            .await
+            .map_err(|e| ApiError::Internal(e.into()))?;
+        let ctx = Arc::clone(&ctx);
+        let principal = Principal::from(&claims);
+        set.spawn(async move {
+            let _permit = permit;
+            (index, create_pseudonyms(&ctx, id_request, &principal).await)
+        });
+    }
+
+    // keep request order
+    results.extend(set.join_all().await);
+    results.sort_by_key(|(index, _)| *index);
+
+    let results = results
+        .into_iter()
+        .map(|(_, res)| match res {
+            Ok(CreateResult::Created(res)) => BatchResult::Created(res),
+            Ok(CreateResult::Prompt(prompt)) => BatchResult::Prompt(prompt),
+            Err(e) => BatchResult::Error {
+                code: e.status().as_u16(),
+                error: e.code(),
+                message: e.detail(),
+            },
+        })
+        .collect();
+
+    Ok((StatusCode::OK, Json(BatchResponse { results })))
+}
+
+enum CreateResult {
+    Created(IdResponse),
+    Prompt(PromptResponse),
+}
+
+async fn create_pseudonyms(
+    ctx: &ApiContext,
+    payload: IdRequest,
+    principal: &Principal,
+) -> Result<CreateResult, ApiError> {
+    let client = &ctx.client;
+
+    // normalize and validate IDAT
+    let payload = IdRequest {
+        idat: validation::normalize(payload.idat),
+        ..payload
+    };
+
+    // E-PIX domain and source of the trial or request
+    let scope = client.epix_scope(&payload.trial, payload.source.as_deref());
+    validation::validate(&payload.idat, client.required_fields(&scope.domain))?;
+
+    check_provisioned(ctx, &payload.trial, &payload.lab).await?;
+
+    check_source(client, &payload.trial, &scope)?;
+
     // get/create mpi in epix
-    let res = ctx.client.add_person(payload.clone()).await?;
+    let res = client.add_person(&scope, payload.clone()).await?;
 
     // parse response
-    match match_status(&res)? {
-        MatchStatus::PossibleMatch => {
+    match match_status(&res).map_err(ApiError::InvalidTtpResponse)? {
+        // multiple matching persons are prompted like a possible match
+        status @ (MatchStatus::PossibleMatch | MatchStatus::MultipleMatch) => {
             // get possible matches
-            let mut mpi = parse_mpi(&res)?;
-            let possible_matches = ctx.client.possible_matches_for_person(mpi.clone()).await?;
+            let mpi = parse_mpi(&res).map_err(ApiError::InvalidTtpResponse)?;
+            let possible_matches = client
+                .possible_matches_for_person(&scope.domain, mpi.clone())
+                .await?;
 
             // newly created identity_id
-            let identity_id = parse_identity_id(&res)?;
+            let identity_id = parse_identity_id(&res).map_err(ApiError::InvalidTtpResponse)?;
+
+            // the identity is only new and safe to delete or split if E-PIX linked it to the
+            // candidates, which is not documented for multiple matches
+            let linked = possible_matches
+                .iter()
+                .any(|m| m.assigned_identity.identity_id == identity_id);
+            if !linked {
+                warn!(
+                    "E-PIX addPerson returned {status} without possible matches of identity {identity_id}, the identity is kept"
+                );
+            }
 
             // resolve match
-            if let Some(link) = &payload.link {
-                if link.merge {
-                    // delete newly created entity
-                    ctx.client.delete_identity(identity_id.parse()?).await?;
-
-                    // matched mpi
-                    mpi = possible_matches
-                        .into_iter()
-                        .find_map(|p| {
-                            if p.matching_identity.identity.identity_id == link.id {
-                                return Some(p.matching_identity.mpi_id.value);
-                            }
-                            None
-                        })
-                        .ok_or(ApiError(
-                            anyhow!("Link.id {} does not match with provided idat", link.id),
-                            StatusCode::NOT_FOUND,
-                        ))?;
-                } else {
-                    // dont merge: remove possible matches
-                    for p in possible_matches {
-                        ctx.client.split_identities(p.link_id).await?;
-                    }
-                }
+            if let Some(link) = &payload.link
+                && linked
+            {
+                let mpi = resolve_match(client, link, identity_id, mpi, possible_matches).await?;
+                info!(
+                    "{principal} {} identity {identity_id} with identity {} in trial {}",
+                    link_decision(link),
+                    link.id,
+                    payload.trial
+                );
 
                 // create pseudonyms
-                let (participant, lab) = ctx.client.pseudonymize(mpi, payload).await?;
-                Ok((StatusCode::OK, Json(IdResponse { participant, lab })).into_response())
+                let event = AuditEvent::new(principal, AuditAction::Create, Some(&payload.trial))
+                    .with_status(&status)
+                    .with_link(AuditLink {
+                        possible_match: None,
+                        identity: Some(identity_id),
+                        with_identity: link.id,
+                        merge: link.merge,
+                    });
+                let response = pseudonymize(
+                    ctx,
+                    mpi,
+                    &payload.trial,
+                    &payload.lab,
+                    link_identity(link, identity_id),
+                    event,
+                )
+                .await?;
+                info!(
+                    "{principal} created pseudonyms of a participant in trial {}",
+                    payload.trial
+                );
+
+                Ok(CreateResult::Created(response))
             } else {
                 // or prompt for matches:
 
-                // delete newly created entity
-                ctx.client.delete_identity(identity_id.parse()?).await?;
+                let token = if !linked {
+                    // unlinked candidates are left to data stewards
+                    None
+                } else if payload.defer {
+                    // keep newly created entity for later resolution
+                    let token = ctx
+                        .pending
+                        .insert(PendingMatch {
+                            identity_id,
+                            mpi,
+                            trial: payload.trial.clone(),
+                            lab: payload.lab,
+                            source: payload.source,
+                        })
+                        .await?;
+                    Some(token)
+                } else {
+                    // delete newly created entity
+                    client.delete_identity(identity_id).await?;
+                    None
+                };
 
                 // return conflicting match
                 let matches = possible_matches
                     .into_iter()
-                    .map(|m| m.matching_identity.identity.into())
+                    .map(|m| IdMatch::candidate(&payload.idat, m))
                     .collect::<Vec<IdMatch>>();
 
-                let prompt_response = PromptResponse { matches };
-
-                Ok((StatusCode::CONFLICT, Json(prompt_response)).into_response())
+                info!(
+                    "{principal} was prompted with {} possible matches in trial {}",
+                    matches.len(),
+                    payload.trial
+                );
+                ctx.audit
+                    .record(
+                        &AuditEvent::new(principal, AuditAction::Create, Some(&payload.trial))
+                            .with_status(&status),
+                    )
+                    .await?;
+                Ok(CreateResult::Prompt(PromptResponse {
+                    matches,
+                    token,
+                    unlinked: !linked,
+                }))
             }
         }
-        MatchStatus::NoMatch | MatchStatus::PerfectMatch => {
+        status @ (MatchStatus::NoMatch | MatchStatus::PerfectMatch) => {
             // parse mpi from response
-            let mpi = parse_mpi(&res)?;
+            let mpi = parse_mpi(&res).map_err(ApiError::InvalidTtpResponse)?;
+
+            // only a new identity is rolled back on failure
+            let new_identity = match status {
+                MatchStatus::NoMatch => {
+                    Some(parse_identity_id(&res).map_err(ApiError::InvalidTtpResponse)?)
+                }
+                _ => None,
+            };
 
             // create pseudonyms
-            let (participant, lab) = ctx.client.pseudonymize(mpi.clone(), payload).await?;
+            let event = AuditEvent::new(principal, AuditAction::Create, Some(&payload.trial))
+                .with_status(&status);
+            let response =
+                pseudonymize(ctx, mpi, &payload.trial, &payload.lab, new_identity, event).await?;
+            info!(
+                "{principal} created pseudonyms of a participant in trial {} ({status})",
+                payload.trial
+            );
+
+            Ok(CreateResult::Created(response))
+        }
+        status @ (MatchStatus::Match | MatchStatus::PerfectMatchWithUpdate) => {
+            // IDAT was added to the matching person
+            let mpi = parse_mpi(&res).map_err(ApiError::InvalidTtpResponse)?;
+
+            // create pseudonyms of the existing mpi
+            let event = AuditEvent::new(principal, AuditAction::Create, Some(&payload.trial))
+                .with_status(&status);
+            let response =
+                pseudonymize(ctx, mpi, &payload.trial, &payload.lab, None, event).await?;
+            info!(
+                "{principal} created pseudonyms of a participant in trial {} (IDAT updated)",
+                payload.trial
+            );
+
+            Ok(CreateResult::Created(IdResponse {
+                idat_updated: true,
+                ..response
+            }))
+        }
+        m => Err(ApiError::MatchError(format!(
+            "E-PIX addPerson failed with unexpected match status: {m}"
+        ))),
+    }
+}
+
+/// Reject unknown data sources and sources the trial may not use
+fn check_source(client: &TtpClient, trial: &str, scope: &EpixScope) -> Result<(), ApiError> {
+    if !client.is_data_source(trial, &scope.source) {
+        return Err(ApiError::BadRequest(format!(
+            "Unknown E-PIX data source of trial {trial}: {}",
+            scope.source
+        )));
+    }
+
+    Ok(())
+}
+
+/// Create pseudonyms and record them in the audit trail. A newly created identity and its
+/// pseudonyms are rolled back if any step fails, so no pseudonyms are returned without an
+/// audit entry.
+async fn pseudonymize(
+    ctx: &ApiContext,
+    mpi: String,
+    trial: &str,
+    lab: &HashMap<String, u32>,
+    new_identity: Option<u32>,
+    event: AuditEvent,
+) -> Result<IdResponse, ApiError> {
+    let client = &ctx.client;
+    let mut saga = Saga::new(client);
+    if let Some(identity_id) = new_identity {
+        saga.record(Step::Identity(identity_id));
+    }
+
+    // create trial and lab domains
+    if !client.is_strict() {
+        saga.run(client.setup_gpas_domains(trial, lab.keys()))
+            .await?;
+    }
+
+    // pseudonyms of an existing mpi may predate this request
+    let created = |domain: String| {
+        new_identity.map(|_| Step::Pseudonyms {
+            domain,
+            mpi: mpi.clone(),
+        })
+    };
+
+    // pseudonymize mpi
+    let participant = saga
+        .run(client.pseudonymize_mpi(trial.to_string(), mpi.clone()))
+        .await?;
+    if let Some(step) = created(trial.to_string()) {
+        saga.record(step);
+    }
+
+    // pseudonymize lab ids with mpi value
+    let mut lab_psns = HashMap::new();
+    for (domain, count) in lab.iter().filter(|(_, count)| **count > 0) {
+        let psns = saga
+            .run(client.pseudonymize_secondary(trial, domain, mpi.clone(), count.to_string()))
+            .await?;
+        if let Some(step) = created(format!("{trial}_{domain}")) {
+            saga.record(step);
+        }
+        lab_psns.insert(domain.clone(), psns);
+    }
+
+    let response = IdResponse {
+        participant,
+        lab: lab_psns,
+        idat_updated: false,
+    };
+    saga.run(async { ctx.audit.record(&event.with_response(&response)).await })
+        .await?;
+
+    Ok(response)
+}
+
+fn link_decision(link: &Link) -> &'static str {
+    if link.merge { "merged" } else { "split" }
+}
+
+/// The new identity is kept as a separate person if the possible match is split
+fn link_identity(link: &Link, identity_id: u32) -> Option<u32> {
+    if link.merge { None } else { Some(identity_id) }
+}
+
+/// Merge or split a possible match and return the resulting mpi
+async fn resolve_match(
+    client: &TtpClient,
+    link: &Link,
+    identity_id: u32,
+    mpi: String,
+    possible_matches: Vec<PossibleMatchResult>,
+) -> Result<String, ApiError> {
+    if link.merge {
+        // matched mpi
+        let mpi = possible_matches
+            .into_iter()
+            .find_map(|p| {
+                if p.matching_identity.identity.identity_id == link.id {
+                    return Some(p.matching_identity.mpi_id.value);
+                }
+                None
+            })
+            .ok_or(ApiError::NotFound(format!(
+                "Link.id {} does not match with provided idat",
+                link.id
+            )))?;
+
+        // delete newly created entity
+        client.delete_identity(identity_id).await?;
 
-            Ok((StatusCode::OK, Json(IdResponse { participant, lab })).into_response())
+        Ok(mpi)
+    } else {
+        // dont merge: remove possible matches
+        for p in possible_matches {
+            client.split_identities(p.link_id).await?;
         }
-        m => Err(anyhow!(
-            "E-PIX addPerson failed with unexpected MatchError: {m}"
-        ))?,
+
+        Ok(mpi)
     }
 }
 
+/// Get open possible matches of all E-PIX domains
+#[debug_handler]
+#[utoipa::path(
+    get,
+    path = "/api/matches",
+    responses(
+        (status = 200, body = Vec<OpenMatch>),
+        (status = 401),
+        (status = 403, body = ProblemDetails, content_type = "application/problem+json"),
+        (status = "5XX", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    security(
+        ("oauth" = []),
+    )
+)]
+pub(crate) async fn list_matches(
+    State(ctx): State<Arc<ApiContext>>,
+    claims: Option<Claims>,
+) -> Result<impl IntoResponse, ApiError> {
+    ctx.access.check_steward(claims.as_ref())?;
+
+    let mut matches = Vec::new();
+    for domain in ctx.client.epix_domains() {
+        matches.extend(
+            ctx.client
+                .possible_matches_for_domain(domain)
+                .await?
+                .into_iter()
+                .map(|m| OpenMatch::new(domain, m)),
+        );
+    }
+
+    Ok((StatusCode::OK, Json(matches)))
+}
+
+/// Resolve a deferred possible match
+///
+/// Pseudonyms are created for the trial and labs of the original request.
+#[debug_handler]
+#[utoipa::path(
+    post,
+    path = "/api/matches/pending/{token}", params(
+        ("token" = String, Path, description = "Resolution token of the deferred possible match"),
+    ),
+    request_body(
+        content = Link,
+        description = "Match resolution",
+        content_type = "application/json"
+    ),
+    responses(
+        (status = 200, body = IdResponse),
+        (status = 401),
+        (status = 403, body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, body = ProblemDetails, content_type = "application/problem+json"),
+        (status = "5XX", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    security(
+        ("oauth" = []),
+    )
+)]
+pub(crate) async fn resolve_pending(
+    State(ctx): State<Arc<ApiContext>>,
+    claims: Option<Claims>,
+    Path(token): Path<String>,
+    ApiJson(link): ApiJson<Link>,
+) -> Result<impl IntoResponse, ApiError> {
+    ctx.access.check_steward(claims.as_ref())?;
+    let principal = Principal::from(&claims);
+
+    let (created, pending) = ctx.pending.take(&token).await?.ok_or(ApiError::NotFound(
+        "No possible match found for token".to_string(),
+    ))?;
+
+    // resolve match
+    let mpi = match resolve_deferred(&ctx, &link, &pending).await {
+        Ok(mpi) => mpi,
+        Err(e) => {
+            // allow retries
+            if let Err(r) = ctx.pending.restore(token, created, pending.clone()).await {
+                warn!(
+                    "Failed to restore deferred identity {} of trial {}: {r}",
+                    pending.identity_id, pending.trial
+                );
+            }
+            return Err(e);
+        }
+    };
+    info!(
+        "{principal} {} deferred identity {} with identity {} in trial {}",
+        link_decision(&link),
+        pending.identity_id,
+        link.id,
+        pending.trial
+    );
+
+    // create pseudonyms
+    let event = AuditEvent::new(&principal, AuditAction::Resolve, Some(&pending.trial)).with_link(
+        AuditLink {
+            possible_match: None,
+            identity: Some(pending.identity_id),
+            with_identity: link.id,
+            merge: link.merge,
+        },
+    );
+    let response = pseudonymize(
+        &ctx,
+        mpi,
+        &pending.trial,
+        &pending.lab,
+        link_identity(&link, pending.identity_id),
+        event,
+    )
+    .await?;
+
+    Ok((StatusCode::OK, Json(response)))
+}
+
+async fn resolve_deferred(
+    ctx: &ApiContext,
+    link: &Link,
+    pending: &PendingMatch,
+) -> Result<String, ApiError> {
+    // get possible matches
+    let scope = ctx
+        .client
+        .epix_scope(&pending.trial, pending.source.as_deref());
+    let possible_matches = ctx
+        .client
+        .possible_matches_for_person(&scope.domain, pending.mpi.clone())
+        .await?;
+
+    resolve_match(
+        &ctx.client,
+        link,
+        pending.identity_id,
+        pending.mpi.clone(),
+        possible_matches,
+    )
+    .await
+}
+
+/// Resolve an open possible match
+///
+/// `Link.id` is the winning identity when merging.
+#[debug_handler]
+#[utoipa::path(
+    post,
+    path = "/api/matches/{link_id}", params(
+        ("link_id" = u32, Path, description = "E-PIX link id of the open possible match"),
+    ),
+    request_body(
+        content = Link,
+        description = "Match resolution",
+        content_type = "application/json"
+    ),
+    responses(
+        (status = 204),
+        (status = 401),
+        (status = 403, body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, body = ProblemDetails, content_type = "application/problem+json"),
+        (status = "5XX", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    security(
+        ("oauth" = []),
+    )
+)]
+pub(crate) async fn resolve_open(
+    State(ctx): State<Arc<ApiContext>>,
+    claims: Option<Claims>,
+    Path(link_id): Path<u32>,
+    ApiJson(link): ApiJson<Link>,
+) -> Result<impl IntoResponse, ApiError> {
+    ctx.access.check_steward(claims.as_ref())?;
+    let principal = Principal::from(&claims);
+
+    // link ids are unique across E-PIX domains
+    let mut possible_match = None;
+    for domain in ctx.client.epix_domains() {
+        possible_match = ctx
+            .client
+            .possible_matches_for_domain(domain)
+            .await?
+            .into_iter()
+            .find(|m| m.link_id == link_id);
+        if possible_match.is_some() {
+            break;
+        }
+    }
+    let possible_match = possible_match.ok_or(ApiError::NotFound(format!(
+        "No open possible match found for link id {link_id}"
+    )))?;
+
+    if link.merge {
+        // winning identity must be part of the match
+        if !possible_match
+            .matching_identities
+            .iter()
+            .any(|m| m.identity.identity_id == link.id)
+        {
+            return Err(ApiError::NotFound(format!(
+                "Link.id {} does not match with possible match",
+                link.id
+            )));
+        }
+        ctx.client.assign_identity(link_id, link.id).await?;
+    } else {
+        ctx.client.split_identities(link_id).await?;
+    }
+    info!(
+        "{principal} {} open possible match {link_id} with identity {}",
+        link_decision(&link),
+        link.id
+    );
+    ctx.audit
+        .record(
+            &AuditEvent::new(&principal, AuditAction::Resolve, None).with_link(AuditLink {
+                possible_match: Some(link_id),
+                identity: None,
+                with_identity: link.id,
+                merge: link.merge,
+            }),
+        )
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Get trials created by this service or configured
+///
+/// Only trials the principal has access to are listed.
+#[debug_handler]
+#[utoipa::path(
+    get,
+    path = "/api/trials",
+    responses(
+        (status = 200, body = Vec<TrialSummary>),
+        (status = 401),
+        (status = "5XX", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    security(
+        ("oauth" = []),
+    )
+)]
+pub(crate) async fn list_trials(
+    State(ctx): State<Arc<ApiContext>>,
+    claims: Option<Claims>,
+) -> Result<impl IntoResponse, ApiError> {
+    // only trials the principal has access to
+    let trials = ctx
+        .client
+        .list_trials()
+        .await?
+        .iter()
+        .filter(|d| ctx.access.trial_grant(claims.as_ref(), &d.name).is_ok())
+        .map(TrialSummary::from)
+        .collect::<Vec<_>>();
+
+    Ok((StatusCode::OK, Json(trials)))
+}
+
+/// Get a trial with its lab domains
+///
+/// Principals with access to some labs of the trial only get these labs.
+#[debug_handler]
+#[utoipa::path(
+    get,
+    path = "/api/trials/{trial}", params(
+        ("trial" = String, Path, description = "The trial"),
+    ),
+    responses(
+        (status = 200, body = TrialResponse),
+        (status = 401),
+        (status = 403, body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, body = ProblemDetails, content_type = "application/problem+json"),
+        (status = "5XX", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    security(
+        ("oauth" = []),
+    )
+)]
+pub(crate) async fn read_trial(
+    State(ctx): State<Arc<ApiContext>>,
+    claims: Option<Claims>,
+    Path(trial): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let grant = ctx.access.trial_grant(claims.as_ref(), &trial)?;
+    let mut trial = get_trial(&ctx, trial).await?;
+    // only labs the principal has access to
+    trial.labs.retain(|l| grant.allows_lab(&l.name));
+
+    Ok((StatusCode::OK, Json(trial)))
+}
+
+/// Provision a trial with its labs
+#[debug_handler]
+#[utoipa::path(
+    post,
+    path = "/api/trials",
+    request_body(
+        content = TrialRequest,
+        description = "Trial and its allowed labs",
+        content_type = "application/json"
+    ),
+    responses(
+        (status = 201, body = TrialResponse),
+        (status = 401),
+        (status = 403),
+        (status = "5XX", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    security(
+        ("oauth" = ["admin"]),
+    )
+)]
+pub(crate) async fn create_trial(
+    State(ctx): State<Arc<ApiContext>>,
+    claims: Option<Claims>,
+    ApiJson(payload): ApiJson<TrialRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    ctx.client
+        .setup_gpas_domains(&payload.name, payload.labs.iter())
+        .await?;
+    let principal = Principal::from(&claims);
+    info!("{principal} provisioned trial {}", payload.name);
+    ctx.audit
+        .record(
+            &AuditEvent::new(&principal, AuditAction::CreateTrial, Some(&payload.name))
+                .with_labs(&payload.labs),
+        )
+        .await?;
+
+    let trial = get_trial(&ctx, payload.name).await?;
+
+    Ok((StatusCode::CREATED, Json(trial)))
+}
+
+/// Get audited operations
+///
+/// `valid` reports whether the hash chain of the audit trail is intact. The whole chain is verified
+/// on startup, later requests only verify the entries added since the last verification and
+/// check that no earlier entries were removed. The `head` hash can be stored externally to detect
+/// a truncated or replaced trail.
+#[debug_handler]
+#[utoipa::path(
+    get,
+    path = "/api/audit",
+    params(AuditQuery),
+    responses(
+        (status = 200, body = AuditResponse),
+        (status = 401),
+        (status = 403),
+        (status = "5XX", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    security(
+        ("oauth" = ["admin"]),
+    )
+)]
+pub(crate) async fn audit(
+    State(ctx): State<Arc<ApiContext>>,
+    Query(query): Query<AuditQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let response = AuditResponse {
+        valid: ctx.audit.verify().await?,
+        head: ctx.audit.head().await?,
+        entries: ctx.audit.query(&query).await?,
+    };
+
+    Ok((StatusCode::OK, Json(response)))
+}
+
+async fn get_trial(ctx: &ApiContext, trial: String) -> Result<TrialResponse, ApiError> {
+    let domain = ctx
+        .client
+        .get_domain(trial.clone())
+        .await?
+        // lab domains are no trials
+        .filter(|d| d.parent_domain_names.is_none())
+        .ok_or(ApiError::NotFound(format!("Trial not found: {trial}")))?;
+
+    // lab domains
+    let lab_prefix = format!("{trial}_");
+    let mut labs = Vec::new();
+    for name in domain.child_domain_names.iter().flatten() {
+        let Some(lab) = ctx.client.get_domain(name.clone()).await? else {
+            continue;
+        };
+        labs.push(LabDomain {
+            name: name.strip_prefix(&lab_prefix).unwrap_or(name).to_string(),
+            label: lab.label.clone(),
+            config: (&lab).into(),
+        });
+    }
+
+    Ok(TrialResponse {
+        name: domain.name.clone(),
+        label: domain.label.clone(),
+        config: (&domain).into(),
+        labs,
+    })
+}
+
+/// Reject trials and labs which were not provisioned (strict mode only)
+async fn check_provisioned(
+    ctx: &ApiContext,
+    trial: &str,
+    lab: &HashMap<String, u32>,
+) -> Result<(), ApiError> {
+    if !ctx.client.is_strict() {
+        return Ok(());
+    }
+
+    let lab_domains = ctx
+        .client
+        .get_domain(trial.to_string())
+        .await?
+        .filter(|d| d.parent_domain_names.is_none())
+        .ok_or(ApiError::BadRequest(format!("Unknown trial: {trial}")))?
+        .child_domain_names
+        .unwrap_or_default();
+
+    let mut unknown = lab
+        .keys()
+        .filter(|l| !lab_domains.contains(&format!("{trial}_{l}")))
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    if !unknown.is_empty() {
+        unknown.sort();
+        return Err(ApiError::BadRequest(format!(
+            "Unknown lab for trial {trial}: {}",
+            unknown.join(", ")
+        )));
+    }
+
+    Ok(())
+}
+
 /// Get all pseudonyms for a participant and a trial
 #[debug_handler]
 #[utoipa::path(
@@ -128,7 +1025,9 @@ pub(crate) async fn create(
     responses(
         (status = 200, body = IdResponse),
         (status = 401),
-        (status = 404)
+        (status = 403, body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, body = ProblemDetails, content_type = "application/problem+json"),
+        (status = "5XX", body = ProblemDetails, content_type = "application/problem+json")
     ),
     security(
         ("oauth" = []),
@@ -136,34 +1035,367 @@ pub(crate) async fn create(
 )]
 pub(crate) async fn read(
     State(ctx): State<Arc<ApiContext>>,
+    claims: Option<Claims>,
     Path((trial, psn)): Path<(String, String)>,
 ) -> Result<impl IntoResponse, ApiError> {
+    let grant = ctx.access.trial_grant(claims.as_ref(), &trial)?;
+
     // get mpi
     let mpi = ctx
         .client
         .identify(trial.clone(), psn.clone())
         .await
-        .map_err(|_| {
-            ApiError(
-                anyhow!("No pseudonyms found for trial and psn"),
-                StatusCode::NOT_FOUND,
-            )
-        })?;
+        .map_err(|e| ApiError::from(e).or_not_found("No pseudonyms found for trial and psn"))?;
 
-    // get domains
-    let domains = ctx.client.get_secondary_domains(trial.clone()).await?;
+    // get domains, restricted to the labs of the principal
+    let domains = ctx
+        .client
+        .get_secondary_domains(trial.clone())
+        .await?
+        .into_iter()
+        .filter(|d| {
+            d.strip_prefix(&format!("{trial}_"))
+                .is_some_and(|lab| grant.allows_lab(lab))
+        })
+        .collect();
 
     // get pseudonyms
     let client: Arc<TtpClient> = Arc::new(ctx.client.clone());
     let lab = client.get_pseudonyms(domains, mpi).await?;
+    let principal = Principal::from(&claims);
+    info!("{principal} read pseudonyms of a participant in trial {trial}");
+
+    let response = IdResponse {
+        participant: psn,
+        lab,
+        idat_updated: false,
+    };
+    ctx.audit
+        .record(
+            &AuditEvent::new(&principal, AuditAction::Read, Some(&trial)).with_response(&response),
+        )
+        .await?;
+
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// Create additional lab pseudonyms for an existing participant
+#[debug_handler]
+#[utoipa::path(
+    post,
+    path = "/api/pseudonyms/{trial}/{psn}/lab", params(
+        ("trial" = String, Path, description = "The trial"),
+        ("psn" = String, Path, description = "Participant pseudonym"),
+    ),
+    request_body(
+        content = LabRequest,
+        description = "Number of pseudonyms to create per lab",
+        content_type = "application/json"
+    ),
+    responses(
+        (status = 200, body = IdResponse),
+        (status = 400, body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401),
+        (status = 403, body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, body = ProblemDetails, content_type = "application/problem+json"),
+        (status = "5XX", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    security(
+        ("oauth" = []),
+    )
+)]
+pub(crate) async fn add_lab(
+    State(ctx): State<Arc<ApiContext>>,
+    claims: Option<Claims>,
+    Path((trial, psn)): Path<(String, String)>,
+    ApiJson(payload): ApiJson<LabRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    ctx.access
+        .check_labs(claims.as_ref(), &trial, payload.lab.keys())?;
+    check_provisioned(&ctx, &trial, &payload.lab).await?;
+
+    // get mpi
+    let mpi = ctx
+        .client
+        .identify(trial.clone(), psn.clone())
+        .await
+        .map_err(|e| ApiError::from(e).or_not_found("No pseudonyms found for trial and psn"))?;
+
+    // create lab pseudonyms
+    let lab = ctx
+        .client
+        .add_lab_pseudonyms(&trial, mpi, &payload.lab)
+        .await?;
+    let principal = Principal::from(&claims);
+    info!("{principal} created lab pseudonyms of a participant in trial {trial}");
+
+    let response = IdResponse {
+        participant: psn,
+        lab,
+        idat_updated: false,
+    };
+    ctx.audit
+        .record(
+            &AuditEvent::new(&principal, AuditAction::AddLab, Some(&trial))
+                .with_response(&response),
+        )
+        .await?;
+
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// Get the participant pseudonym for a lab pseudonym
+#[debug_handler]
+#[utoipa::path(
+    get,
+    path = "/api/pseudonyms/{trial}/lab/{lab}/{psn}", params(
+        ("trial" = String, Path, description = "The trial"),
+        ("lab" = String, Path, description = "The lab"),
+        ("psn" = String, Path, description = "Lab pseudonym"),
+    ),
+    responses(
+        (status = 200, body = IdResponse),
+        (status = 401),
+        (status = 403, body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, body = ProblemDetails, content_type = "application/problem+json"),
+        (status = "5XX", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    security(
+        ("oauth" = []),
+    )
+)]
+pub(crate) async fn read_lab(
+    State(ctx): State<Arc<ApiContext>>,
+    claims: Option<Claims>,
+    Path((trial, lab, psn)): Path<(String, String, String)>,
+) -> Result<impl IntoResponse, ApiError> {
+    ctx.access
+        .check_labs(claims.as_ref(), &trial, std::iter::once(&lab))?;
+
+    // get mpi from lab domain
+    let mpi = ctx
+        .client
+        .identify(format!("{trial}_{lab}"), psn.clone())
+        .await
+        .map_err(|e| {
+            ApiError::from(e).or_not_found("No pseudonyms found for trial, lab and psn")
+        })?;
+
+    // get participant pseudonym
+    let participant = ctx
+        .client
+        .get_pseudonym(trial.clone(), mpi)
+        .await
+        .map_err(|e| {
+            ApiError::from(e).or_not_found("No participant pseudonym found for lab psn")
+        })?;
+    let principal = Principal::from(&claims);
+    info!("{principal} looked up the participant of a lab {lab} pseudonym in trial {trial}");
+
+    let response = IdResponse {
+        participant,
+        lab: HashMap::from([(lab, vec![psn])]),
+        idat_updated: false,
+    };
+    ctx.audit
+        .record(
+            &AuditEvent::new(&principal, AuditAction::ReadLab, Some(&trial))
+                .with_response(&response),
+        )
+        .await?;
+
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// Update identifying data of a participant
+#[debug_handler]
+#[utoipa::path(
+    put,
+    path = "/api/participants/{trial}/{psn}", params(
+        ("trial" = String, Path, description = "The trial"),
+        ("psn" = String, Path, description = "Participant pseudonym"),
+        UpdateParams,
+    ),
+    request_body(
+        content = Idat,
+        description = "Updated participant data",
+        content_type = "application/json"
+    ),
+    responses(
+        (status = 200, body = Idat),
+        (status = 400, body = ProblemDetails, content_type = "application/problem+json", description = "Unknown data source"),
+        (status = 401),
+        (status = 403, body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, body = ProblemDetails, content_type = "application/problem+json", description = "Invalid IDAT"),
+        (status = "5XX", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    security(
+        ("oauth" = []),
+    )
+)]
+pub(crate) async fn update_participant(
+    State(ctx): State<Arc<ApiContext>>,
+    claims: Option<Claims>,
+    Path((trial, psn)): Path<(String, String)>,
+    Query(params): Query<UpdateParams>,
+    ApiJson(idat): ApiJson<Idat>,
+) -> Result<impl IntoResponse, ApiError> {
+    ctx.access.check_trial(claims.as_ref(), &trial)?;
+    let idat = validation::normalize(idat);
+    let scope = ctx.client.epix_scope(&trial, params.source.as_deref());
+    validation::validate(&idat, ctx.client.required_fields(&scope.domain))?;
+    check_source(&ctx.client, &trial, &scope)?;
+
+    // get mpi
+    let mpi = ctx
+        .client
+        .identify(trial.clone(), psn.clone())
+        .await
+        .map_err(|e| ApiError::from(e).or_not_found("No participant found for trial and psn"))?;
+
+    // update identity
+    let person = ctx.client.update_person(&scope, mpi, &idat).await?;
+    let principal = Principal::from(&claims);
+    info!("{principal} updated IDAT of a participant in trial {trial}");
+    ctx.audit
+        .record(
+            &AuditEvent::new(&principal, AuditAction::Update, Some(&trial)).with_pseudonym(&psn),
+        )
+        .await?;
+
+    // stored IDAT
+    Ok((StatusCode::OK, Json(Idat::from(person))))
+}
+
+/// Delete a participant (withdrawal of consent)
+#[debug_handler]
+#[utoipa::path(
+    delete,
+    path = "/api/participants/{trial}/{psn}", params(
+        ("trial" = String, Path, description = "The trial"),
+        ("psn" = String, Path, description = "Participant pseudonym"),
+        DeleteParams,
+    ),
+    responses(
+        (status = 204),
+        (status = 401),
+        (status = 403, body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, body = ProblemDetails, content_type = "application/problem+json", description = "Identity is used by other trials"),
+        (status = "5XX", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    security(
+        ("oauth" = []),
+    )
+)]
+pub(crate) async fn delete_participant(
+    State(ctx): State<Arc<ApiContext>>,
+    claims: Option<Claims>,
+    Path((trial, psn)): Path<(String, String)>,
+    Query(params): Query<DeleteParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    ctx.access.check_trial(claims.as_ref(), &trial)?;
+
+    // get mpi
+    let mpi = ctx
+        .client
+        .identify(trial.clone(), psn.clone())
+        .await
+        .map_err(|e| ApiError::from(e).or_not_found("No participant found for trial and psn"))?;
+
+    // get identity before its pseudonyms are gone
+    let scope = ctx.client.epix_scope(&trial, None);
+    let identity = match params.identity {
+        Some(action) => {
+            // identity is still referenced by other trials
+            let others = ctx.client.other_pseudonym_domains(&trial, &mpi).await?;
+            if !others.is_empty() {
+                return Err(ApiError::Conflict(format!(
+                    "Participant has pseudonyms in {} other gPAS domains, its identity is kept",
+                    others.len()
+                )));
+            }
+
+            // including identities merged into the person
+            Some((
+                action,
+                ctx.client
+                    .person_identities(&scope.domain, mpi.clone())
+                    .await?,
+            ))
+        }
+        None => None,
+    };
+
+    // delete trial and lab pseudonyms
+    ctx.client.delete_pseudonyms(trial.clone(), mpi).await?;
+    let principal = Principal::from(&claims);
+    info!("{principal} deleted a participant in trial {trial}");
+    ctx.audit
+        .record(
+            &AuditEvent::new(&principal, AuditAction::Delete, Some(&trial)).with_pseudonym(&psn),
+        )
+        .await?;
+
+    // deactivate or delete identities
+    if let Some((action, ids)) = identity {
+        for id in ids {
+            match action {
+                IdentityAction::Deactivate => ctx.client.deactivate_identity(id).await?,
+                IdentityAction::Delete => ctx.client.delete_identity(id).await?,
+            }
+        }
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Get identifying data of a participant
+#[debug_handler]
+#[utoipa::path(
+    get,
+    path = "/api/participants/{trial}/{psn}/idat", params(
+        ("trial" = String, Path, description = "The trial"),
+        ("psn" = String, Path, description = "Participant pseudonym"),
+    ),
+    responses(
+        (status = 200, body = Idat),
+        (status = 401),
+        (status = 403),
+        (status = 404, body = ProblemDetails, content_type = "application/problem+json"),
+        (status = "5XX", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    security(
+        ("oauth" = ["idat"]),
+    )
+)]
+pub(crate) async fn read_idat(
+    State(ctx): State<Arc<ApiContext>>,
+    claims: Option<Claims>,
+    Path((trial, psn)): Path<(String, String)>,
+) -> Result<impl IntoResponse, ApiError> {
+    ctx.access.check_trial(claims.as_ref(), &trial)?;
+    let scope = ctx.client.epix_scope(&trial, None);
+
+    // get mpi
+    let mpi = ctx
+        .client
+        .identify(trial.clone(), psn.clone())
+        .await
+        .map_err(|e| ApiError::from(e).or_not_found("No participant found for trial and psn"))?;
+
+    // get identity
+    let identity = ctx.client.get_person(&scope.domain, mpi).await?;
+    let principal = Principal::from(&claims);
+    info!("{principal} re-identified a participant in trial {trial}");
+    ctx.audit
+        .record(
+            &AuditEvent::new(&principal, AuditAction::ReadIdat, Some(&trial)).with_pseudonym(&psn),
+        )
+        .await?;
 
-    Ok((
-        StatusCode::OK,
-        Json(IdResponse {
-            participant: psn,
-            lab,
-        }),
-    ))
+    Ok((StatusCode::OK, Json(Idat::from(identity))))
 }
 
 fn match_result(params: &Parameters) -> impl Iterator<Item = &ParametersParameter> {
@@ -239,7 +1471,7 @@ fn parse_mpi(params: &Parameters) -> Result<String, anyhow::Error> {
         ))
 }
 
-fn parse_identity_id(params: &Parameters) -> Result<String, anyhow::Error> {
+fn parse_identity_id(params: &Parameters) -> Result<u32, anyhow::Error> {
     // mpi person resource
     match_result(params)
         .filter_map(|part| {
@@ -255,5 +1487,7 @@ fn parse_identity_id(params: &Parameters) -> Result<String, anyhow::Error> {
         })
         .flatten()
         .next()
-        .ok_or(anyhow!("Failed to parse person_id from E-PIX response"))
+        .ok_or(anyhow!("Failed to parse person_id from E-PIX response"))?
+        .parse()
+        .map_err(|e| anyhow!("Failed to parse person_id from E-PIX response: {e}"))
 }
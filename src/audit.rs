@@ -0,0 +1,511 @@
+use crate::config::Audit;
+use crate::model::{
+    AuditAction, AuditEntry, AuditEvent, AuditLink, AuditQuery, IdResponse, MatchStatus,
+};
+use anyhow::anyhow;
+use auth::oauth::Claims;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use log::{info, warn};
+use rusqlite::{params, Connection};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// Previous hash of the first entry
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Principal of a request, `anonymous` if authentication is disabled
+#[derive(Clone, Debug)]
+pub(crate) struct Principal {
+    sub: String,
+    name: Option<String>,
+    display: String,
+}
+
+impl From<&Option<Claims>> for Principal {
+    fn from(claims: &Option<Claims>) -> Self {
+        match claims {
+            Some(claims) => Principal {
+                sub: claims.sub.clone(),
+                name: claims.name().map(str::to_string),
+                display: claims.to_string(),
+            },
+            None => Principal {
+                sub: "anonymous".to_string(),
+                name: None,
+                display: "anonymous".to_string(),
+            },
+        }
+    }
+}
+
+impl Principal {
+    /// Subject of the access token or `anonymous`
+    pub(crate) fn subject(&self) -> &str {
+        &self.sub
+    }
+}
+
+impl fmt::Display for Principal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.display)
+    }
+}
+
+impl AuditEvent {
+    pub(crate) fn new(principal: &Principal, action: AuditAction, trial: Option<&str>) -> Self {
+        AuditEvent {
+            time: Utc::now(),
+            principal: principal.sub.clone(),
+            principal_name: principal.name.clone(),
+            action,
+            trial: trial.map(str::to_string),
+            labs: vec![],
+            status: None,
+            pseudonyms: vec![],
+            link: None,
+        }
+    }
+
+    pub(crate) fn with_status(self, status: &MatchStatus) -> Self {
+        AuditEvent {
+            status: Some(status.to_string()),
+            ..self
+        }
+    }
+
+    pub(crate) fn with_link(self, link: AuditLink) -> Self {
+        AuditEvent {
+            link: Some(link),
+            ..self
+        }
+    }
+
+    pub(crate) fn with_labs(self, labs: &[String]) -> Self {
+        AuditEvent {
+            labs: labs.to_vec(),
+            ..self
+        }
+    }
+
+    /// Event with a pseudonym, which is hashed when recorded
+    pub(crate) fn with_pseudonym(self, psn: &str) -> Self {
+        AuditEvent {
+            pseudonyms: vec![psn.to_string()],
+            ..self
+        }
+    }
+
+    /// Event with the labs and pseudonyms of a response, which are hashed when recorded
+    pub(crate) fn with_response(self, response: &IdResponse) -> Self {
+        let mut labs = response.lab.iter().collect::<Vec<_>>();
+        labs.sort();
+
+        AuditEvent {
+            labs: labs.iter().map(|(lab, _)| lab.to_string()).collect(),
+            pseudonyms: std::iter::once(&response.participant)
+                .chain(labs.iter().flat_map(|(_, psns)| psns.iter()))
+                .cloned()
+                .collect(),
+            ..self
+        }
+    }
+}
+
+fn sha256(value: &str) -> String {
+    format!("{:x}", Sha256::digest(value))
+}
+
+fn entry_hash(prev_hash: &str, record: &str) -> String {
+    sha256(&format!("{prev_hash}{record}"))
+}
+
+/// Append-only audit trail in a SQLite database. Each entry is chained to its predecessor by
+/// its hash, so modified or removed entries are detected by [`AuditLog::verify`].
+/// Pseudonyms are hashed with a secret key, so they cannot be recovered by hashing guessed values.
+#[derive(Clone)]
+pub(crate) struct AuditLog {
+    store: Arc<Mutex<Store>>,
+    mac: Hmac<Sha256>,
+}
+
+struct Store {
+    conn: Connection,
+    /// Last verified entry, later verifications only check the entries after it
+    checkpoint: Checkpoint,
+}
+
+struct Checkpoint {
+    id: i64,
+    hash: String,
+    /// Number of entries up to the checkpoint, to detect removed entries
+    count: i64,
+}
+
+impl Default for Checkpoint {
+    fn default() -> Self {
+        Checkpoint {
+            id: 0,
+            hash: GENESIS_HASH.to_string(),
+            count: 0,
+        }
+    }
+}
+
+impl AuditLog {
+    pub(crate) fn new(config: &Audit) -> anyhow::Result<Self> {
+        let path = config
+            .path
+            .as_ref()
+            .ok_or(anyhow!("Audit trail file (audit.path) is not configured"))?;
+        let secret = config
+            .secret
+            .as_ref()
+            .filter(|s| !s.is_empty())
+            .ok_or(anyhow!("Audit secret key (audit.secret) is not configured"))?;
+        let mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())?;
+
+        let conn = Connection::open(path)?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS audit (
+                id        INTEGER PRIMARY KEY AUTOINCREMENT,
+                time      INTEGER NOT NULL,
+                principal TEXT NOT NULL,
+                trial     TEXT,
+                record    TEXT NOT NULL,
+                prev_hash TEXT NOT NULL,
+                hash      TEXT NOT NULL
+            );
+            CREATE TRIGGER IF NOT EXISTS audit_no_update BEFORE UPDATE ON audit
+            BEGIN SELECT RAISE(ABORT, 'audit trail is append-only'); END;
+            CREATE TRIGGER IF NOT EXISTS audit_no_delete BEFORE DELETE ON audit
+            BEGIN SELECT RAISE(ABORT, 'audit trail is append-only'); END;",
+        )?;
+
+        // the whole chain is verified once on startup
+        let mut store = Store {
+            conn,
+            checkpoint: Checkpoint::default(),
+        };
+        if !store.verify()? {
+            warn!("Audit trail hash chain is broken");
+        }
+        if let Some(head) = last_hash(&store.conn)? {
+            info!("Audit trail head: {head}");
+        }
+
+        Ok(AuditLog {
+            store: Arc::new(Mutex::new(store)),
+            mac,
+        })
+    }
+
+    /// Run a query on a blocking thread, so the runtime is not blocked by file I/O
+    async fn with_store<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut Store) -> anyhow::Result<T> + Send + 'static,
+    ) -> anyhow::Result<T> {
+        let store = Arc::clone(&self.store);
+        tokio::task::spawn_blocking(move || {
+            let mut store = store
+                .lock()
+                .map_err(|_| anyhow!("Audit trail is poisoned"))?;
+            f(&mut store)
+        })
+        .await?
+    }
+
+    /// Append an event with hashed pseudonyms to the audit trail
+    pub(crate) async fn record(&self, event: &AuditEvent) -> anyhow::Result<()> {
+        let event = AuditEvent {
+            pseudonyms: event.pseudonyms.iter().map(|p| self.hash(p)).collect(),
+            ..event.clone()
+        };
+        let record = serde_json::to_string(&event)?;
+
+        self.with_store(move |store| {
+            let prev_hash = last_hash(&store.conn)?.unwrap_or(GENESIS_HASH.to_string());
+            store.conn.execute(
+                "INSERT INTO audit (time, principal, trial, record, prev_hash, hash)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    event.time.timestamp_millis(),
+                    event.principal,
+                    event.trial,
+                    record,
+                    prev_hash,
+                    entry_hash(&prev_hash, &record),
+                ],
+            )?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    fn hash(&self, psn: &str) -> String {
+        let mut mac = self.mac.clone();
+        mac.update(psn.as_bytes());
+        format!("{:x}", mac.finalize().into_bytes())
+    }
+
+    /// Hash of the latest entry
+    pub(crate) async fn head(&self) -> anyhow::Result<Option<String>> {
+        self.with_store(|store| last_hash(&store.conn)).await
+    }
+
+    pub(crate) async fn query(&self, query: &AuditQuery) -> anyhow::Result<Vec<AuditEntry>> {
+        let query = query.clone();
+        self.with_store(move |store| {
+            let mut stmt = store.conn.prepare(
+                "SELECT id, record, prev_hash, hash FROM audit
+                 WHERE (?1 IS NULL OR trial = ?1)
+                   AND (?2 IS NULL OR principal = ?2)
+                   AND (?3 IS NULL OR time >= ?3)
+                   AND (?4 IS NULL OR time <= ?4)
+                 ORDER BY id",
+            )?;
+            let rows = stmt.query_map(
+                params![
+                    query.trial,
+                    query.principal,
+                    query.from.map(|t| t.timestamp_millis()),
+                    query.to.map(|t| t.timestamp_millis()),
+                ],
+                |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, String>(3)?,
+                    ))
+                },
+            )?;
+
+            rows.map(|row| {
+                let (id, record, prev_hash, hash) = row?;
+                Ok(AuditEntry {
+                    id,
+                    event: serde_json::from_str(&record)?,
+                    prev_hash,
+                    hash,
+                })
+            })
+            .collect()
+        })
+        .await
+    }
+
+    /// Check the hash chain of the entries added since the last verification
+    pub(crate) async fn verify(&self) -> anyhow::Result<bool> {
+        self.with_store(Store::verify).await
+    }
+}
+
+impl Store {
+    /// Verify the entries after the checkpoint and move it to the latest entry. Entries up to the
+    /// checkpoint are only checked for removal, changes to them are detected on the next start.
+    fn verify(&mut self) -> anyhow::Result<bool> {
+        let Checkpoint { id, hash, count } = &self.checkpoint;
+        let verified: (i64, Option<String>) = self.conn.query_row(
+            "SELECT COUNT(*), MAX(CASE WHEN id = ?1 THEN hash END) FROM audit WHERE id <= ?1",
+            params![id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        if verified.0 != *count || (*id > 0 && verified.1.as_ref() != Some(hash)) {
+            return Ok(false);
+        }
+
+        let mut stmt = self.conn.prepare(
+            "SELECT id, time, principal, trial, record, prev_hash, hash FROM audit
+             WHERE id > ?1 ORDER BY id",
+        )?;
+        let mut rows = stmt.query(params![id])?;
+
+        let mut checkpoint = Checkpoint {
+            id: *id,
+            hash: hash.clone(),
+            count: *count,
+        };
+        while let Some(row) = rows.next()? {
+            let record: String = row.get(4)?;
+            let prev_hash: String = row.get(5)?;
+            let hash: String = row.get(6)?;
+            if prev_hash != checkpoint.hash || hash != entry_hash(&prev_hash, &record) {
+                return Ok(false);
+            }
+
+            // filtered columns must match the hashed event
+            let event: AuditEvent = serde_json::from_str(&record)?;
+            if row.get::<_, i64>(1)? != event.time.timestamp_millis()
+                || row.get::<_, String>(2)? != event.principal
+                || row.get::<_, Option<String>>(3)? != event.trial
+            {
+                return Ok(false);
+            }
+            checkpoint = Checkpoint {
+                id: row.get(0)?,
+                hash,
+                count: checkpoint.count + 1,
+            };
+        }
+        drop(rows);
+        drop(stmt);
+
+        self.checkpoint = checkpoint;
+        Ok(true)
+    }
+}
+
+fn last_hash(conn: &Connection) -> anyhow::Result<Option<String>> {
+    let mut stmt = conn.prepare("SELECT hash FROM audit ORDER BY id DESC LIMIT 1")?;
+    let mut rows = stmt.query([])?;
+
+    Ok(match rows.next()? {
+        Some(row) => Some(row.get(0)?),
+        None => None,
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::audit::{AuditLog, Principal};
+    use crate::config::Audit;
+    use crate::model::{AuditAction, AuditEvent, AuditQuery, IdResponse};
+    use auth::oauth::Claims;
+    use std::collections::HashMap;
+
+    /// In-memory audit trail
+    pub(crate) fn setup_config() -> Audit {
+        Audit {
+            path: Some(":memory:".to_string()),
+            secret: Some("secret".to_string()),
+        }
+    }
+
+    async fn setup_log() -> AuditLog {
+        let log = AuditLog::new(&setup_config()).unwrap();
+        let alice = Principal::from(&Some(Claims {
+            sub: "1234".to_string(),
+            preferred_username: Some("alice".to_string()),
+            ..Default::default()
+        }));
+        let anonymous = Principal::from(&None);
+
+        let created = AuditEvent::new(&alice, AuditAction::Create, Some("trial")).with_response(
+            &IdResponse {
+                participant: "psn".to_string(),
+                lab: HashMap::from([("trial_lab".to_string(), vec!["lab_psn".to_string()])]),
+                idat_updated: false,
+            },
+        );
+        log.record(&created).await.unwrap();
+        log.record(&AuditEvent::new(
+            &anonymous,
+            AuditAction::Read,
+            Some("other"),
+        ))
+        .await
+        .unwrap();
+        log.record(&AuditEvent::new(&alice, AuditAction::Resolve, None))
+            .await
+            .unwrap();
+
+        log
+    }
+
+    #[tokio::test]
+    async fn test_query() {
+        let log = setup_log().await;
+
+        let all = log.query(&AuditQuery::default()).await.unwrap();
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[1].prev_hash, all[0].hash);
+        assert_eq!(log.head().await.unwrap(), Some(all[2].hash.clone()));
+
+        // pseudonyms are hashed
+        assert_eq!(all[0].event.labs, vec!["trial_lab"]);
+        assert_eq!(all[0].event.pseudonyms.len(), 2);
+        assert!(!all[0].event.pseudonyms.contains(&"psn".to_string()));
+        assert_ne!(all[0].event.pseudonyms[0], super::sha256("psn"));
+        assert_eq!(all[0].event.principal_name.as_deref(), Some("alice"));
+
+        let by_principal = log
+            .query(&AuditQuery {
+                principal: Some("1234".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(by_principal.len(), 2);
+
+        let by_trial = log
+            .query(&AuditQuery {
+                trial: Some("other".to_string()),
+                from: Some(all[0].event.time),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(by_trial.len(), 1);
+        assert_eq!(by_trial[0].event.principal, "anonymous");
+    }
+
+    #[test]
+    fn test_required_config() {
+        assert!(AuditLog::new(&Audit::default()).is_err());
+        assert!(
+            AuditLog::new(&Audit {
+                secret: None,
+                ..setup_config()
+            })
+            .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tamper_evidence() {
+        let log = setup_log().await;
+        assert!(log.verify().await.unwrap());
+        assert_eq!(log.store.lock().unwrap().checkpoint.id, 3);
+
+        {
+            let conn = &log.store.lock().unwrap().conn;
+            // entries cannot be changed or removed
+            assert!(
+                conn.execute("UPDATE audit SET trial = 'changed' WHERE id = 1", [])
+                    .is_err()
+            );
+            assert!(conn.execute("DELETE FROM audit WHERE id = 2", []).is_err());
+            conn.execute_batch("DROP TRIGGER audit_no_update; DROP TRIGGER audit_no_delete;")
+                .unwrap();
+        }
+
+        // entries after the checkpoint are verified
+        log.record(&AuditEvent::new(
+            &Principal::from(&None),
+            AuditAction::Read,
+            None,
+        ))
+        .await
+        .unwrap();
+        log.store
+            .lock()
+            .unwrap()
+            .conn
+            .execute("UPDATE audit SET principal = 'changed' WHERE id = 4", [])
+            .unwrap();
+        assert!(!log.verify().await.unwrap());
+        assert_eq!(log.store.lock().unwrap().checkpoint.id, 3);
+
+        // removed entries before the checkpoint break the chain
+        log.store
+            .lock()
+            .unwrap()
+            .conn
+            .execute("DELETE FROM audit WHERE id IN (2, 4)", [])
+            .unwrap();
+        assert!(!log.verify().await.unwrap());
+    }
+}
@@ -1,5 +1,6 @@
 use config::{Config, ConfigError, Environment, File};
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::option::Option;
 
 #[derive(Default, Deserialize, Clone)]
@@ -7,6 +8,100 @@ pub(crate) struct AppConfig {
     pub(crate) log_level: String,
     pub(crate) auth: Option<Auth>,
     pub(crate) ttp: Ttp,
+    #[serde(default)]
+    pub(crate) trials: HashMap<String, Trial>,
+    #[serde(default)]
+    pub(crate) idempotency: Idempotency,
+    #[serde(default)]
+    pub(crate) pending: Pending,
+    #[serde(default)]
+    pub(crate) audit: Audit,
+}
+
+#[derive(Default, Deserialize, Clone, Debug)]
+pub(crate) struct Audit {
+    /// SQLite database file, required
+    pub(crate) path: Option<String>,
+    /// Secret key of the pseudonym hashes, required
+    pub(crate) secret: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub(crate) struct Idempotency {
+    /// Seconds after which stored responses expire
+    #[serde(default = "default_idempotency_ttl")]
+    pub(crate) ttl: u64,
+    /// Seconds after which a key still in progress is taken over by a retry, e.g. after a crash
+    #[serde(default = "default_idempotency_lease")]
+    pub(crate) lease: u64,
+    /// SQLite database file. Responses are kept in memory if not set
+    pub(crate) path: Option<String>,
+}
+
+impl Default for Idempotency {
+    fn default() -> Self {
+        Idempotency {
+            ttl: default_idempotency_ttl(),
+            lease: default_idempotency_lease(),
+            path: None,
+        }
+    }
+}
+
+fn default_idempotency_ttl() -> u64 {
+    24 * 60 * 60
+}
+
+fn default_idempotency_lease() -> u64 {
+    5 * 60
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub(crate) struct Pending {
+    /// Seconds after which deferred possible matches expire
+    #[serde(default = "default_pending_ttl")]
+    pub(crate) ttl: u64,
+    /// SQLite database file. Deferred possible matches are kept in memory if not set
+    pub(crate) path: Option<String>,
+}
+
+impl Default for Pending {
+    fn default() -> Self {
+        Pending {
+            ttl: default_pending_ttl(),
+            path: None,
+        }
+    }
+}
+
+fn default_pending_ttl() -> u64 {
+    24 * 60 * 60
+}
+
+#[derive(Default, Deserialize, Clone, Debug)]
+pub(crate) struct Trial {
+    #[serde(flatten)]
+    pub(crate) domain: DomainSettings,
+    #[serde(default)]
+    pub(crate) labs: HashMap<String, DomainSettings>,
+    /// E-PIX domain of the trial. Defaults to `ttp.epix.domain`
+    pub(crate) epix_domain: Option<String>,
+    /// E-PIX data source of the trial. Defaults to `ttp.epix.data_source`
+    pub(crate) data_source: Option<String>,
+    /// E-PIX data sources requests of the trial may use besides its own. Any source if empty
+    #[serde(default)]
+    pub(crate) sources: Vec<String>,
+}
+
+/// gPAS domain settings. Unset values use the service defaults
+#[derive(Default, Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct DomainSettings {
+    pub(crate) label: Option<String>,
+    pub(crate) prefix: Option<String>,
+    pub(crate) alphabet: Option<String>,
+    pub(crate) check_digit_class: Option<String>,
+    pub(crate) psn_length: Option<i32>,
+    pub(crate) psns_deletable: Option<bool>,
 }
 
 #[derive(Default, Deserialize, Clone)]
@@ -17,7 +112,76 @@ pub(crate) struct Auth {
 #[derive(Default, Deserialize, Clone)]
 pub(crate) struct Oidc {
     pub(crate) client_id: String,
+    /// Client secret, required for token introspection
+    pub(crate) client_secret: Option<String>,
     pub(crate) issuer_url: String,
+    #[serde(default)]
+    pub(crate) token_validation: TokenValidation,
+    /// Seconds to cache active tokens in introspection mode
+    #[serde(default = "default_introspection_cache_ttl")]
+    pub(crate) introspection_cache_ttl: u64,
+    /// Accepted audiences of access tokens. Defaults to the client id
+    #[serde(default)]
+    pub(crate) audiences: Vec<String>,
+    /// Seconds of clock skew allowed for JWT expiry
+    #[serde(default = "default_leeway")]
+    pub(crate) leeway: u64,
+    /// Claims which must be present in access tokens
+    #[serde(default)]
+    pub(crate) required_claims: Vec<String>,
+    /// Accepted authorized parties (`azp`) of access tokens. Not checked if empty
+    #[serde(default)]
+    pub(crate) authorized_parties: Vec<String>,
+    #[serde(default = "default_idat_scope")]
+    pub(crate) idat_scope: String,
+    #[serde(default = "default_admin_scope")]
+    pub(crate) admin_scope: String,
+    /// Grant required to resolve possible matches. Not restricted if not set
+    pub(crate) steward_role: Option<String>,
+    /// Access rules by trial. Trials without rules are not restricted unless `deny_by_default`
+    #[serde(default)]
+    pub(crate) access: HashMap<String, TrialAccess>,
+    /// Deny access to trials without access rules
+    #[serde(default)]
+    pub(crate) deny_by_default: bool,
+}
+
+/// Validation of access tokens
+#[derive(Default, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum TokenValidation {
+    /// Local validation of JWT access tokens with the keys of the issuer
+    #[default]
+    Jwt,
+    /// Token introspection at the issuer (RFC 7662)
+    Introspection,
+}
+
+/// Grants (scopes or roles) with access to a trial
+#[derive(Default, Deserialize, Clone, Debug)]
+pub(crate) struct TrialAccess {
+    /// Grants with access to the whole trial
+    #[serde(default)]
+    pub(crate) grants: Vec<String>,
+    /// Grants restricted to a lab of the trial
+    #[serde(default)]
+    pub(crate) labs: HashMap<String, Vec<String>>,
+}
+
+fn default_idat_scope() -> String {
+    "idat".to_string()
+}
+
+fn default_admin_scope() -> String {
+    "admin".to_string()
+}
+
+fn default_introspection_cache_ttl() -> u64 {
+    30
+}
+
+fn default_leeway() -> u64 {
+    60
 }
 
 #[derive(Default, Deserialize, Clone)]
@@ -33,17 +197,50 @@ pub(crate) struct Epix {
     pub(crate) domain: Domain,
     pub(crate) identifier_domain: String,
     pub(crate) data_source: String,
+    /// Additional E-PIX domains
+    #[serde(default)]
+    pub(crate) domains: Vec<Domain>,
+    /// Additional E-PIX data sources
+    #[serde(default)]
+    pub(crate) data_sources: Vec<String>,
+    /// Matching config file. Defaults to `resources/matching_config.xml`
+    pub(crate) matching_config: Option<String>,
+    /// Update the matching config of existing domains if it differs from the file
+    #[serde(default)]
+    pub(crate) update_matching_config: bool,
 }
 
 #[derive(Default, Deserialize, Clone, Debug)]
 pub(crate) struct Domain {
     pub(crate) name: String,
     pub(crate) description: String,
+    /// Matching config file of the domain. Defaults to `ttp.epix.matching_config`
+    pub(crate) matching_config: Option<String>,
 }
 
-#[derive(Default, Deserialize, Clone, Debug)]
+#[derive(Deserialize, Clone, Debug)]
 pub(crate) struct Gpas {
     pub(crate) base_url: String,
+    /// Required to roll back pseudonyms of failed requests
+    #[serde(default = "default_psns_deletable")]
+    pub(crate) psns_deletable: bool,
+    /// Only allow provisioned trials and labs
+    #[serde(default)]
+    pub(crate) strict: bool,
+}
+
+impl Default for Gpas {
+    fn default() -> Self {
+        Gpas {
+            base_url: String::new(),
+            psns_deletable: default_psns_deletable(),
+            strict: false,
+        }
+    }
+}
+
+fn default_psns_deletable() -> bool {
+    true
 }
 
 impl AppConfig {
@@ -1,11 +1,233 @@
-use axum::http::StatusCode;
+use crate::ttp::client::{FaultException, SoapFault};
+use crate::ttp::gpas::OperationError;
+use anyhow::anyhow;
+use axum::extract::rejection::JsonRejection;
+use axum::http::{header, StatusCode};
 use axum::response::{IntoResponse, Response};
+use log::error;
+use serde::Serialize;
 
-pub(crate) struct ApiError(pub anyhow::Error, pub StatusCode);
+/// Stable error codes of problem details
+#[derive(utoipa::ToSchema, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ErrorCode {
+    TtpUnavailable,
+    InvalidTtpResponse,
+    DomainInUse,
+    DuplicateEntry,
+    InvalidParameter,
+    UnknownValue,
+    UnknownDomain,
+    MatchError,
+    BadRequest,
+    ValidationFailed,
+    Forbidden,
+    NotFound,
+    Conflict,
+    IdempotencyKeyReused,
+    InternalError,
+}
+
+/// Problem details according to RFC 9457
+#[derive(utoipa::ToSchema, Serialize)]
+pub(crate) struct ProblemDetails {
+    /// Problem type, derived from the error code
+    #[serde(rename = "type")]
+    pub(crate) problem_type: String,
+    pub(crate) title: String,
+    pub(crate) status: u16,
+    pub(crate) detail: String,
+    pub(crate) code: ErrorCode,
+    /// Invalid fields of the request
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(crate) errors: Vec<FieldError>,
+}
+
+/// Validation error of a single request field
+#[derive(utoipa::ToSchema, Serialize, Clone, Debug, PartialEq)]
+pub(crate) struct FieldError {
+    pub(crate) field: String,
+    pub(crate) message: String,
+}
+
+#[derive(Debug)]
+pub(crate) enum ApiError {
+    /// E-PIX or gPAS could not be reached
+    TtpUnavailable(anyhow::Error),
+    /// E-PIX or gPAS returned a response which could not be parsed
+    InvalidTtpResponse(anyhow::Error),
+    /// SOAP fault returned by E-PIX or gPAS
+    Fault(SoapFault),
+    /// Error of a gPAS FHIR operation
+    Operation(OperationError),
+    /// E-PIX returned an unexpected match status
+    MatchError(String),
+    /// Malformed request
+    BadRequest(String),
+    /// Request data is invalid
+    Validation(String),
+    /// Participant data failed field validation
+    InvalidIdat(Vec<FieldError>),
+    /// Principal is not allowed to access the resource
+    Forbidden(String),
+    NotFound(String),
+    Conflict(String),
+    /// Idempotency key was used with a different request
+    IdempotencyKeyReused,
+    Internal(anyhow::Error),
+}
+
+impl ApiError {
+    pub(crate) fn code(&self) -> ErrorCode {
+        match self {
+            ApiError::TtpUnavailable(_) => ErrorCode::TtpUnavailable,
+            ApiError::InvalidTtpResponse(_) => ErrorCode::InvalidTtpResponse,
+            ApiError::Fault(f) => match f.fault.detail {
+                FaultException::DomainInUse(_) => ErrorCode::DomainInUse,
+                FaultException::DuplicateEntry(_) => ErrorCode::DuplicateEntry,
+                FaultException::InvalidParameter(_) => ErrorCode::InvalidParameter,
+                FaultException::UnknownValue(_) => ErrorCode::UnknownValue,
+                FaultException::UnknownDomain(_) => ErrorCode::UnknownDomain,
+            },
+            ApiError::Operation(e) => match e {
+                OperationError::UnknownValue(_) => ErrorCode::UnknownValue,
+                OperationError::UnknownDomain(_) => ErrorCode::UnknownDomain,
+                OperationError::Other(_) => ErrorCode::InternalError,
+            },
+            ApiError::MatchError(_) => ErrorCode::MatchError,
+            ApiError::BadRequest(_) => ErrorCode::BadRequest,
+            ApiError::Validation(_) | ApiError::InvalidIdat(_) => ErrorCode::ValidationFailed,
+            ApiError::Forbidden(_) => ErrorCode::Forbidden,
+            ApiError::NotFound(_) => ErrorCode::NotFound,
+            ApiError::Conflict(_) => ErrorCode::Conflict,
+            ApiError::IdempotencyKeyReused => ErrorCode::IdempotencyKeyReused,
+            ApiError::Internal(_) => ErrorCode::InternalError,
+        }
+    }
+
+    pub(crate) fn status(&self) -> StatusCode {
+        match self.code() {
+            ErrorCode::TtpUnavailable | ErrorCode::InvalidTtpResponse | ErrorCode::MatchError => {
+                StatusCode::BAD_GATEWAY
+            }
+            ErrorCode::DomainInUse | ErrorCode::DuplicateEntry | ErrorCode::Conflict => {
+                StatusCode::CONFLICT
+            }
+            ErrorCode::InvalidParameter | ErrorCode::BadRequest => StatusCode::BAD_REQUEST,
+            ErrorCode::Forbidden => StatusCode::FORBIDDEN,
+            ErrorCode::UnknownValue | ErrorCode::UnknownDomain | ErrorCode::NotFound => {
+                StatusCode::NOT_FOUND
+            }
+            ErrorCode::ValidationFailed | ErrorCode::IdempotencyKeyReused => {
+                StatusCode::UNPROCESSABLE_ENTITY
+            }
+            ErrorCode::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub(crate) fn detail(&self) -> String {
+        match self {
+            ApiError::TtpUnavailable(e)
+            | ApiError::InvalidTtpResponse(e)
+            | ApiError::Internal(e) => e.to_string(),
+            ApiError::Fault(f) => f.to_string(),
+            ApiError::Operation(e) => e.to_string(),
+            ApiError::MatchError(d)
+            | ApiError::BadRequest(d)
+            | ApiError::Validation(d)
+            | ApiError::Forbidden(d)
+            | ApiError::NotFound(d)
+            | ApiError::Conflict(d) => d.clone(),
+            ApiError::InvalidIdat(errors) => format!(
+                "Invalid IDAT: {}",
+                errors
+                    .iter()
+                    .map(|e| format!("{} {}", e.field, e.message))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            ApiError::IdempotencyKeyReused => {
+                "Idempotency-Key was already used with a different request body".to_string()
+            }
+        }
+    }
+
+    /// Unknown gPAS values and domains as not found resource, other errors are kept
+    pub(crate) fn or_not_found(self, detail: &str) -> Self {
+        match self.code() {
+            ErrorCode::UnknownValue | ErrorCode::UnknownDomain => {
+                ApiError::NotFound(detail.to_string())
+            }
+            _ => self,
+        }
+    }
+
+    /// Append information to the detail message, keeping the error type
+    pub(crate) fn with_note(self, note: &str) -> Self {
+        match self {
+            ApiError::TtpUnavailable(e) => ApiError::TtpUnavailable(anyhow!("{e}. {note}")),
+            ApiError::InvalidTtpResponse(e) => ApiError::InvalidTtpResponse(anyhow!("{e}. {note}")),
+            ApiError::Internal(e) => ApiError::Internal(anyhow!("{e}. {note}")),
+            ApiError::Fault(mut f) => {
+                f.context = format!("{note}. {}", f.context);
+                ApiError::Fault(f)
+            }
+            ApiError::Operation(e) => ApiError::Operation(match e {
+                OperationError::UnknownValue(m) => {
+                    OperationError::UnknownValue(format!("{m}. {note}"))
+                }
+                OperationError::UnknownDomain(m) => {
+                    OperationError::UnknownDomain(format!("{m}. {note}"))
+                }
+                OperationError::Other(m) => OperationError::Other(format!("{m}. {note}")),
+            }),
+            ApiError::MatchError(d) => ApiError::MatchError(format!("{d}. {note}")),
+            ApiError::BadRequest(d) => ApiError::BadRequest(format!("{d}. {note}")),
+            ApiError::Validation(d) => ApiError::Validation(format!("{d}. {note}")),
+            ApiError::Forbidden(d) => ApiError::Forbidden(format!("{d}. {note}")),
+            ApiError::NotFound(d) => ApiError::NotFound(format!("{d}. {note}")),
+            ApiError::Conflict(d) => ApiError::Conflict(format!("{d}. {note}")),
+            ApiError::InvalidIdat(errors) => ApiError::InvalidIdat(errors),
+            ApiError::IdempotencyKeyReused => ApiError::IdempotencyKeyReused,
+        }
+    }
+
+    pub(crate) fn problem(&self) -> ProblemDetails {
+        let status = self.status();
+        let code = self.code();
+        let code_name = serde_json::to_value(code)
+            .ok()
+            .and_then(|v| v.as_str().map(str::to_string))
+            .unwrap_or_default();
+
+        ProblemDetails {
+            problem_type: format!("urn:ttp-idm:error:{code_name}"),
+            title: status.canonical_reason().unwrap_or_default().to_string(),
+            status: status.as_u16(),
+            detail: self.detail(),
+            code,
+            errors: match self {
+                ApiError::InvalidIdat(errors) => errors.clone(),
+                _ => vec![],
+            },
+        }
+    }
+}
 
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
-        (self.1, format!("{}", self.0)).into_response()
+        if let ApiError::Internal(e) | ApiError::TtpUnavailable(e) = &self {
+            error!("{e:#}");
+        }
+
+        let problem = self.problem();
+        let body = serde_json::to_string(&problem).unwrap_or_default();
+        (
+            self.status(),
+            [(header::CONTENT_TYPE, "application/problem+json")],
+            body,
+        )
+            .into_response()
     }
 }
 
@@ -14,6 +236,92 @@ where
     E: Into<anyhow::Error>,
 {
     fn from(err: E) -> Self {
-        Self(err.into(), StatusCode::INTERNAL_SERVER_ERROR)
+        let err = err.into();
+
+        let err = match err.downcast::<SoapFault>() {
+            Ok(fault) => return ApiError::Fault(fault),
+            Err(err) => err,
+        };
+        // malformed or invalid request bodies
+        let err = match err.downcast::<JsonRejection>() {
+            Ok(e) if e.status() == StatusCode::UNPROCESSABLE_ENTITY => {
+                return ApiError::Validation(e.body_text());
+            }
+            Ok(e) => return ApiError::BadRequest(e.body_text()),
+            Err(err) => err,
+        };
+        let err = match err.downcast::<OperationError>() {
+            Ok(e) => return ApiError::Operation(e),
+            Err(err) => err,
+        };
+        if let Some(e) = err.downcast_ref::<reqwest::Error>() {
+            return if e.is_decode() {
+                ApiError::InvalidTtpResponse(err)
+            } else {
+                ApiError::TtpUnavailable(err)
+            };
+        }
+        if err.is::<serde_json::Error>() || err.is::<serde_xml_rs::Error>() {
+            return ApiError::InvalidTtpResponse(err);
+        }
+
+        ApiError::Internal(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::{ApiError, ErrorCode};
+    use crate::ttp::client::{Fault, FaultException, SoapFault};
+    use anyhow::anyhow;
+    use axum::http::StatusCode;
+
+    #[test]
+    fn test_error_mapping() {
+        let fault: anyhow::Error = SoapFault {
+            context: "Failed to get gPAS domain".to_string(),
+            fault: Fault {
+                faultcode: "soap:Server".to_string(),
+                faultstring: "domain trial not found".to_string(),
+                detail: FaultException::UnknownDomain(()),
+            },
+        }
+        .into();
+        let fault = ApiError::from(fault);
+        assert_eq!(fault.code(), ErrorCode::UnknownDomain);
+        assert_eq!(fault.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            fault.detail(),
+            "Failed to get gPAS domain: domain trial not found"
+        );
+
+        let parse = ApiError::from(serde_json::from_str::<u32>("{").unwrap_err());
+        assert_eq!(parse.code(), ErrorCode::InvalidTtpResponse);
+
+        let internal = ApiError::from(anyhow!("unexpected"));
+        assert_eq!(internal.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        // only unknown values are not found
+        let unknown = fault.or_not_found("No pseudonyms found");
+        assert_eq!(unknown.code(), ErrorCode::NotFound);
+        assert_eq!(unknown.detail(), "No pseudonyms found");
+        let internal = internal.or_not_found("No pseudonyms found");
+        assert_eq!(internal.code(), ErrorCode::InternalError);
+    }
+
+    #[test]
+    fn test_problem_details() {
+        let problem = ApiError::NotFound("No pseudonyms found".to_string()).problem();
+
+        assert_eq!(
+            serde_json::to_value(problem).unwrap(),
+            serde_json::json!({
+                "type": "urn:ttp-idm:error:not_found",
+                "title": "Not Found",
+                "status": 404,
+                "detail": "No pseudonyms found",
+                "code": "not_found"
+            })
+        );
     }
 }
@@ -0,0 +1,432 @@
+use crate::audit::Principal;
+use crate::config::Idempotency;
+use anyhow::anyhow;
+use log::{error, warn};
+use rusqlite::{params, Connection, OptionalExtension};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::RwLock;
+
+/// Attempts to store a response before the key is released
+const COMPLETE_ATTEMPTS: u32 = 3;
+
+/// Response of a completed request
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct StoredResponse {
+    pub(crate) status: u16,
+    pub(crate) body: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Lookup {
+    /// First request with this key
+    New,
+    /// Request was already answered
+    Replay(StoredResponse),
+    /// Request is still being processed
+    InProgress,
+    /// Key was used with a different request body
+    Conflict,
+}
+
+/// Hash of a request body to detect key reuse
+pub(crate) fn hash(body: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(body))
+}
+
+/// Keys are scoped by principal, so responses are never replayed to other clients
+pub(crate) fn scoped_key(principal: &Principal, key: &str) -> String {
+    format!("{}:{key}", hash(principal.subject().as_bytes()))
+}
+
+/// Lookup of a stored key. A key whose lease expired before its response was stored is taken
+/// over as [`Lookup::New`], as its request was abandoned.
+fn lookup(
+    hash: &str,
+    stored_hash: &str,
+    response: Option<StoredResponse>,
+    lease_expired: bool,
+) -> Lookup {
+    if hash != stored_hash {
+        return Lookup::Conflict;
+    }
+    match response {
+        Some(response) => Lookup::Replay(response),
+        None if lease_expired => Lookup::New,
+        None => Lookup::InProgress,
+    }
+}
+
+/// Stores responses by idempotency key, either in memory or in a SQLite file
+#[derive(Clone)]
+pub(crate) enum IdempotencyStore {
+    Memory(MemoryStore),
+    Sqlite(SqliteStore),
+}
+
+impl Default for IdempotencyStore {
+    fn default() -> Self {
+        let config = Idempotency::default();
+        IdempotencyStore::Memory(MemoryStore::new(
+            Duration::from_secs(config.ttl),
+            Duration::from_secs(config.lease),
+        ))
+    }
+}
+
+impl IdempotencyStore {
+    pub(crate) fn new(config: &Idempotency) -> anyhow::Result<Self> {
+        let (ttl, lease) = (
+            Duration::from_secs(config.ttl),
+            Duration::from_secs(config.lease),
+        );
+        match &config.path {
+            Some(path) => Ok(IdempotencyStore::Sqlite(SqliteStore::open(
+                path, ttl, lease,
+            )?)),
+            None => Ok(IdempotencyStore::Memory(MemoryStore::new(ttl, lease))),
+        }
+    }
+
+    /// Look up a key and reserve it for processing if it is new
+    pub(crate) async fn begin(&self, key: &str, hash: &str) -> anyhow::Result<Lookup> {
+        match self {
+            IdempotencyStore::Memory(store) => Ok(store.begin(key, hash).await),
+            IdempotencyStore::Sqlite(store) => store.begin(key, hash).await,
+        }
+    }
+
+    /// Store the response for a reserved key
+    pub(crate) async fn complete(&self, key: &str, response: StoredResponse) -> anyhow::Result<()> {
+        match self {
+            IdempotencyStore::Memory(store) => {
+                store.complete(key, response).await;
+                Ok(())
+            }
+            IdempotencyStore::Sqlite(store) => store.complete(key, response).await,
+        }
+    }
+
+    /// Store the response for a reserved key, retrying failed attempts. If it cannot be stored,
+    /// the key is released, so retries are processed again instead of conflicting until the
+    /// lease expires.
+    pub(crate) async fn finish(&self, key: &str, response: StoredResponse) {
+        for attempt in 1..=COMPLETE_ATTEMPTS {
+            match self.complete(key, response.clone()).await {
+                Ok(()) => return,
+                Err(e) => warn!(
+                    "Failed to store idempotent response (attempt {attempt}/{COMPLETE_ATTEMPTS}): {e}"
+                ),
+            }
+            tokio::time::sleep(Duration::from_millis(100 * attempt as u64)).await;
+        }
+
+        if let Err(e) = self.abort(key).await {
+            error!("Failed to release idempotency key, it is released after its lease: {e}");
+        }
+    }
+
+    /// Release a reserved key so the request can be retried
+    pub(crate) async fn abort(&self, key: &str) -> anyhow::Result<()> {
+        match self {
+            IdempotencyStore::Memory(store) => {
+                store.abort(key).await;
+                Ok(())
+            }
+            IdempotencyStore::Sqlite(store) => store.abort(key).await,
+        }
+    }
+}
+
+struct Entry {
+    /// Reservation time, which starts the lease of a key in progress
+    created: Instant,
+    hash: String,
+    response: Option<StoredResponse>,
+}
+
+#[derive(Clone)]
+pub(crate) struct MemoryStore {
+    ttl: Duration,
+    lease: Duration,
+    entries: Arc<RwLock<HashMap<String, Entry>>>,
+}
+
+impl MemoryStore {
+    fn new(ttl: Duration, lease: Duration) -> Self {
+        MemoryStore {
+            ttl,
+            lease,
+            entries: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    async fn begin(&self, key: &str, hash: &str) -> Lookup {
+        let mut entries = self.entries.write().await;
+        // drop expired keys
+        entries.retain(|_, e| e.created.elapsed() < self.ttl);
+
+        match entries.get_mut(key) {
+            Some(entry) => {
+                let expired = entry.created.elapsed() >= self.lease;
+                let lookup = lookup(hash, &entry.hash, entry.response.clone(), expired);
+                if lookup == Lookup::New {
+                    // renew the lease of the abandoned key
+                    entry.created = Instant::now();
+                }
+                lookup
+            }
+            None => {
+                entries.insert(
+                    key.to_string(),
+                    Entry {
+                        created: Instant::now(),
+                        hash: hash.to_string(),
+                        response: None,
+                    },
+                );
+                Lookup::New
+            }
+        }
+    }
+
+    async fn complete(&self, key: &str, response: StoredResponse) {
+        if let Some(entry) = self.entries.write().await.get_mut(key) {
+            entry.response = Some(response);
+        }
+    }
+
+    async fn abort(&self, key: &str) {
+        self.entries.write().await.remove(key);
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct SqliteStore {
+    ttl: Duration,
+    lease: Duration,
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl SqliteStore {
+    fn open(path: &str, ttl: Duration, lease: Duration) -> anyhow::Result<Self> {
+        let conn = Connection::open(path)?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS idempotency (
+                key     TEXT PRIMARY KEY,
+                hash    TEXT NOT NULL,
+                created INTEGER NOT NULL,
+                status  INTEGER,
+                body    TEXT
+            )",
+            (),
+        )?;
+
+        Ok(SqliteStore {
+            ttl,
+            lease,
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// Run a query on a blocking thread, so the runtime is not blocked by file I/O
+    async fn with_connection<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&Connection) -> anyhow::Result<T> + Send + 'static,
+    ) -> anyhow::Result<T> {
+        let conn = Arc::clone(&self.conn);
+        tokio::task::spawn_blocking(move || {
+            let conn = conn
+                .lock()
+                .map_err(|_| anyhow!("Idempotency store is poisoned"))?;
+            f(&conn)
+        })
+        .await?
+    }
+
+    async fn begin(&self, key: &str, hash: &str) -> anyhow::Result<Lookup> {
+        let (key, hash, ttl, lease) = (key.to_string(), hash.to_string(), self.ttl, self.lease);
+        self.with_connection(move |conn| Self::begin_blocking(conn, &key, &hash, ttl, lease))
+            .await
+    }
+
+    fn begin_blocking(
+        conn: &Connection,
+        key: &str,
+        hash: &str,
+        ttl: Duration,
+        lease: Duration,
+    ) -> anyhow::Result<Lookup> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+
+        // drop expired keys
+        conn.execute(
+            "DELETE FROM idempotency WHERE created <= ?1",
+            params![now - ttl.as_secs() as i64],
+        )?;
+
+        let entry = conn
+            .query_row(
+                "SELECT hash, status, body, created FROM idempotency WHERE key = ?1",
+                params![key],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, Option<u16>>(1)?,
+                        row.get::<_, Option<String>>(2)?,
+                        row.get::<_, i64>(3)?,
+                    ))
+                },
+            )
+            .optional()?;
+
+        match entry {
+            Some((stored_hash, status, body, created)) => {
+                let response = status
+                    .zip(body)
+                    .map(|(status, body)| StoredResponse { status, body });
+                let expired = created <= now - lease.as_secs() as i64;
+                let lookup = lookup(hash, &stored_hash, response, expired);
+                if lookup == Lookup::New {
+                    // renew the lease of the abandoned key
+                    conn.execute(
+                        "UPDATE idempotency SET created = ?2 WHERE key = ?1",
+                        params![key, now],
+                    )?;
+                }
+                Ok(lookup)
+            }
+            None => {
+                conn.execute(
+                    "INSERT INTO idempotency (key, hash, created) VALUES (?1, ?2, ?3)",
+                    params![key, hash, now],
+                )?;
+                Ok(Lookup::New)
+            }
+        }
+    }
+
+    async fn complete(&self, key: &str, response: StoredResponse) -> anyhow::Result<()> {
+        let key = key.to_string();
+        self.with_connection(move |conn| {
+            conn.execute(
+                "UPDATE idempotency SET status = ?2, body = ?3 WHERE key = ?1",
+                params![key, response.status, response.body],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn abort(&self, key: &str) -> anyhow::Result<()> {
+        let key = key.to_string();
+        self.with_connection(move |conn| {
+            conn.execute("DELETE FROM idempotency WHERE key = ?1", params![key])?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Idempotency;
+    use crate::idempotency::{hash, IdempotencyStore, Lookup, StoredResponse};
+
+    async fn assert_store(store: IdempotencyStore) {
+        let response = StoredResponse {
+            status: 200,
+            body: r#"{"participant":"psn"}"#.to_string(),
+        };
+
+        assert_eq!(
+            store.begin("key", &hash(b"body")).await.unwrap(),
+            Lookup::New
+        );
+        assert_eq!(
+            store.begin("key", &hash(b"body")).await.unwrap(),
+            Lookup::InProgress
+        );
+
+        store.complete("key", response.clone()).await.unwrap();
+        assert_eq!(
+            store.begin("key", &hash(b"body")).await.unwrap(),
+            Lookup::Replay(response)
+        );
+        assert_eq!(
+            store.begin("key", &hash(b"other")).await.unwrap(),
+            Lookup::Conflict
+        );
+
+        // aborted keys can be reused
+        store.abort("key").await.unwrap();
+        assert_eq!(
+            store.begin("key", &hash(b"other")).await.unwrap(),
+            Lookup::New
+        );
+    }
+
+    #[tokio::test]
+    async fn test_memory_store() {
+        assert_store(IdempotencyStore::default()).await;
+    }
+
+    #[tokio::test]
+    async fn test_expired_lease() {
+        for path in [None, Some(":memory:".to_string())] {
+            let store = IdempotencyStore::new(&Idempotency {
+                lease: 0,
+                path,
+                ..Default::default()
+            })
+            .unwrap();
+
+            assert_eq!(
+                store.begin("key", &hash(b"body")).await.unwrap(),
+                Lookup::New
+            );
+            // abandoned keys are taken over
+            assert_eq!(
+                store.begin("key", &hash(b"body")).await.unwrap(),
+                Lookup::New
+            );
+            assert_eq!(
+                store.begin("key", &hash(b"other")).await.unwrap(),
+                Lookup::Conflict
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_finish() {
+        let store = IdempotencyStore::new(&Idempotency {
+            path: Some(":memory:".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+        let response = StoredResponse {
+            status: 200,
+            body: "{}".to_string(),
+        };
+
+        store.begin("key", &hash(b"body")).await.unwrap();
+        store.finish("key", response.clone()).await;
+        assert_eq!(
+            store.begin("key", &hash(b"body")).await.unwrap(),
+            Lookup::Replay(response)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_store() {
+        let store = IdempotencyStore::new(&Idempotency {
+            path: Some(":memory:".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert_store(store).await;
+    }
+}
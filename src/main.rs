@@ -4,12 +4,18 @@ use anyhow::anyhow;
 use log::{error, info};
 use shadow_rs::shadow;
 
+mod access;
 mod api;
+mod audit;
 mod config;
 mod error;
+mod idempotency;
 mod model;
+mod pending;
+mod saga;
 mod server;
 mod ttp;
+mod validation;
 
 shadow!(build);
 
@@ -1,9 +1,13 @@
-use crate::ttp::epix::model::MpiIdentity;
+use crate::error::ErrorCode;
+use crate::ttp::epix::model::{MpiIdentity, PossibleMatchForDomain, PossibleMatchResult};
+use crate::ttp::gpas::model::Domain;
 use anyhow::anyhow;
-use chrono::{Datelike, NaiveDate};
-use fhir_model::r4b::codes::NameUse;
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, Utc};
+use fhir_model::r4b::codes::{AdministrativeGender, ContactPointSystem, NameUse};
 use fhir_model::r4b::resources::{Patient, Resource};
-use fhir_model::r4b::types::{Address, Extension, ExtensionValue, HumanName, Meta};
+use fhir_model::r4b::types::{
+    Address, ContactPoint, Extension, ExtensionValue, HumanName, Identifier as FhirIdentifier, Meta,
+};
 use fhir_model::Date;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -65,9 +69,12 @@ impl fmt::Display for MatchStatus {
 pub(crate) struct IdResponse {
     pub(crate) participant: String,
     pub(crate) lab: HashMap<String, Vec<String>>,
+    /// IDAT of the matched participant was updated in E-PIX
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) idat_updated: bool,
 }
 
-#[derive(utoipa::ToSchema, Deserialize, Serialize, Debug, PartialEq, Clone)]
+#[derive(utoipa::ToSchema, Deserialize, Serialize, Debug, PartialEq, Clone, Default)]
 pub(crate) struct Idat {
     pub(crate) first_name: String,
     pub(crate) last_name: String,
@@ -75,19 +82,171 @@ pub(crate) struct Idat {
     pub(crate) birth_place: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub(crate) birth_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) gender: Option<Gender>,
     pub(crate) postal_code: String,
     pub(crate) city: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) street: Option<String>,
+    /// ISO 3166-1 alpha-2 country code
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) country: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) phone: Option<String>,
+    /// External identifiers, e.g. the health insurance number
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) identifiers: Vec<Identifier>,
+}
+
+#[derive(utoipa::ToSchema, Deserialize, Serialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Gender {
+    Male,
+    Female,
+    Other,
+    Unknown,
+}
+
+impl Gender {
+    /// Gender code of E-PIX
+    pub(crate) fn epix_code(&self) -> &'static str {
+        match self {
+            Gender::Male => "M",
+            Gender::Female => "F",
+            Gender::Other => "O",
+            Gender::Unknown => "U",
+        }
+    }
+
+    pub(crate) fn from_epix_code(code: &str) -> Option<Self> {
+        match code {
+            "M" => Some(Gender::Male),
+            "F" => Some(Gender::Female),
+            "O" | "X" | "D" => Some(Gender::Other),
+            "U" => Some(Gender::Unknown),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Gender {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Gender::Male => write!(f, "male"),
+            Gender::Female => write!(f, "female"),
+            Gender::Other => write!(f, "other"),
+            Gender::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+impl From<Gender> for AdministrativeGender {
+    fn from(value: Gender) -> Self {
+        match value {
+            Gender::Male => AdministrativeGender::Male,
+            Gender::Female => AdministrativeGender::Female,
+            Gender::Other => AdministrativeGender::Other,
+            Gender::Unknown => AdministrativeGender::Unknown,
+        }
+    }
+}
+
+#[derive(utoipa::ToSchema, Deserialize, Serialize, Debug, PartialEq, Clone)]
+pub(crate) struct Identifier {
+    /// Name of the E-PIX identifier domain
+    pub(crate) system: String,
+    pub(crate) value: String,
 }
 
 #[derive(utoipa::ToSchema, Deserialize, Serialize)]
 pub(crate) struct PromptResponse {
     pub(crate) matches: Vec<IdMatch>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) token: Option<String>,
+    /// E-PIX did not link a new identity to the candidates, so the match cannot be resolved
+    /// with a `link` and is left to data stewards (`/api/matches`)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) unlinked: bool,
 }
 
 #[derive(utoipa::ToSchema, Deserialize, Serialize)]
 pub(crate) struct IdMatch {
     pub(crate) idat: Idat,
+    /// E-PIX identity id of the candidate, to be used as `Link.id`
+    pub(crate) identity_id: u32,
+    /// E-PIX identity id of the candidate, use `identity_id`
+    #[schema(deprecated)]
+    pub(crate) link_id: u32,
+    /// E-PIX link id of the possible match
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) possible_match_id: Option<u32>,
+    /// Match probability computed by E-PIX
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) probability: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) priority: Option<String>,
+    /// Creation time of the possible match
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) created: Option<DateTime<FixedOffset>>,
+    /// Fields of the submitted IDAT which differ from the candidate
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) diff: Vec<FieldDiff>,
+}
+
+#[derive(utoipa::ToSchema, Deserialize, Serialize, Debug, PartialEq)]
+pub(crate) struct FieldDiff {
+    pub(crate) field: String,
+    pub(crate) submitted: Option<String>,
+    pub(crate) candidate: Option<String>,
+}
+
+#[derive(utoipa::ToSchema, Serialize)]
+pub(crate) struct OpenMatch {
+    /// E-PIX domain of the possible match
+    pub(crate) domain: String,
     pub(crate) link_id: u32,
+    pub(crate) probability: f64,
+    pub(crate) priority: String,
+    pub(crate) identities: Vec<IdMatch>,
+}
+
+#[derive(utoipa::ToSchema, Deserialize)]
+pub(crate) struct TrialRequest {
+    pub(crate) name: String,
+    #[serde(default)]
+    pub(crate) labs: Vec<String>,
+}
+
+#[derive(utoipa::ToSchema, Serialize)]
+pub(crate) struct TrialSummary {
+    pub(crate) name: String,
+    pub(crate) label: Option<String>,
+}
+
+#[derive(utoipa::ToSchema, Serialize)]
+pub(crate) struct TrialResponse {
+    pub(crate) name: String,
+    pub(crate) label: Option<String>,
+    pub(crate) config: DomainInfo,
+    pub(crate) labs: Vec<LabDomain>,
+}
+
+#[derive(utoipa::ToSchema, Serialize)]
+pub(crate) struct LabDomain {
+    pub(crate) name: String,
+    pub(crate) label: Option<String>,
+    pub(crate) config: DomainInfo,
+}
+
+/// Pseudonym settings of a gPAS domain
+#[derive(utoipa::ToSchema, Serialize)]
+pub(crate) struct DomainInfo {
+    pub(crate) alphabet: String,
+    pub(crate) check_digit_class: String,
+    pub(crate) psn_length: i32,
+    pub(crate) prefix: Option<String>,
+    pub(crate) psns_deletable: bool,
+    pub(crate) multi_psn: bool,
 }
 
 #[derive(utoipa::ToSchema, Deserialize, Clone)]
@@ -102,6 +261,140 @@ pub(crate) struct IdRequest {
     pub(crate) trial: String,
     pub(crate) lab: HashMap<String, u32>,
     pub(crate) link: Option<Link>,
+    #[serde(default)]
+    pub(crate) defer: bool,
+    /// E-PIX data source (sending site). Defaults to the data source of the trial
+    pub(crate) source: Option<String>,
+}
+
+#[derive(utoipa::ToSchema, Serialize)]
+pub(crate) struct BatchResponse {
+    pub(crate) results: Vec<BatchResult>,
+}
+
+#[derive(utoipa::ToSchema, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub(crate) enum BatchResult {
+    Created(IdResponse),
+    Prompt(PromptResponse),
+    Error {
+        code: u16,
+        error: ErrorCode,
+        message: String,
+    },
+}
+
+#[derive(utoipa::IntoParams, Deserialize)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct UpdateParams {
+    /// E-PIX data source (sending site). Defaults to the data source of the trial
+    pub(crate) source: Option<String>,
+}
+
+#[derive(utoipa::IntoParams, Deserialize)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct DeleteParams {
+    /// Deactivate or delete all E-PIX identities of the participant's person
+    pub(crate) identity: Option<IdentityAction>,
+}
+
+#[derive(utoipa::ToSchema, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum IdentityAction {
+    Deactivate,
+    Delete,
+}
+
+#[derive(utoipa::ToSchema, Deserialize, Clone)]
+pub(crate) struct LabRequest {
+    pub(crate) lab: HashMap<String, u32>,
+}
+
+/// Audited operation
+#[derive(utoipa::ToSchema, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum AuditAction {
+    Create,
+    Read,
+    ReadLab,
+    AddLab,
+    Resolve,
+    ReadIdat,
+    Update,
+    Delete,
+    CreateTrial,
+}
+
+/// Decision on a possible match
+#[derive(utoipa::ToSchema, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct AuditLink {
+    /// E-PIX link id of an open possible match
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) possible_match: Option<u32>,
+    /// New identity of the participant
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) identity: Option<u32>,
+    /// Matching identity
+    pub(crate) with_identity: u32,
+    pub(crate) merge: bool,
+}
+
+/// Recorded operation, the hashed content of an audit entry
+#[derive(utoipa::ToSchema, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct AuditEvent {
+    pub(crate) time: DateTime<Utc>,
+    /// Subject of the principal, `anonymous` if authorization is disabled
+    pub(crate) principal: String,
+    /// User name or client of the principal
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) principal_name: Option<String>,
+    pub(crate) action: AuditAction,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) trial: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) labs: Vec<String>,
+    /// E-PIX match status of created pseudonyms
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) status: Option<String>,
+    /// HMAC-SHA256 hashes of the resulting pseudonyms, keyed with `audit.secret`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) pseudonyms: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) link: Option<AuditLink>,
+}
+
+/// Entry of the hash chained audit trail
+#[derive(utoipa::ToSchema, Serialize, Debug, PartialEq)]
+pub(crate) struct AuditEntry {
+    pub(crate) id: i64,
+    #[serde(flatten)]
+    pub(crate) event: AuditEvent,
+    /// Hash of the previous entry
+    pub(crate) prev_hash: String,
+    /// SHA-256 of the previous hash and the event
+    pub(crate) hash: String,
+}
+
+#[derive(utoipa::IntoParams, Deserialize, Default, Clone)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct AuditQuery {
+    pub(crate) trial: Option<String>,
+    /// Subject of the principal
+    pub(crate) principal: Option<String>,
+    /// Start of the time range (inclusive)
+    pub(crate) from: Option<DateTime<Utc>>,
+    /// End of the time range (inclusive)
+    pub(crate) to: Option<DateTime<Utc>>,
+}
+
+#[derive(utoipa::ToSchema, Serialize)]
+pub(crate) struct AuditResponse {
+    /// The hash chain of the whole audit trail is intact
+    pub(crate) valid: bool,
+    /// Hash of the latest entry
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) head: Option<String>,
+    pub(crate) entries: Vec<AuditEntry>,
 }
 
 impl TryInto<Patient> for IdRequest {
@@ -132,7 +425,44 @@ impl TryInto<Patient> for IdRequest {
             ));
         }
 
-        let builder = Patient::builder()
+        // address
+        let mut address = Address::builder()
+            .postal_code(self.idat.postal_code)
+            .city(self.idat.city);
+        if let Some(street) = self.idat.street {
+            address = address.line(vec![Some(street)]);
+        }
+        if let Some(country) = self.idat.country {
+            address = address.country(country);
+        }
+
+        // (optional) phone
+        let telecom = self
+            .idat
+            .phone
+            .map(|phone| {
+                ContactPoint::builder()
+                    .system(ContactPointSystem::Phone)
+                    .value(phone)
+                    .build()
+            })
+            .transpose()?;
+
+        // external identifiers
+        let identifiers = self
+            .idat
+            .identifiers
+            .into_iter()
+            .map(|i| {
+                FhirIdentifier::builder()
+                    .system(i.system)
+                    .value(i.value)
+                    .build()
+                    .map(Some)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut builder = Patient::builder()
             .meta(
                 Meta::builder()
                     .profile(vec![Some(
@@ -143,12 +473,9 @@ impl TryInto<Patient> for IdRequest {
             )
             .name(names)
             .birth_date(Date::Date(parse_date(self.idat.birth_date)?))
-            .address(vec![Some(
-                Address::builder()
-                    .postal_code(self.idat.postal_code)
-                    .city(self.idat.city)
-                    .build()?,
-            )])
+            .address(vec![Some(address.build()?)])
+            .telecom(telecom.into_iter().map(Some).collect())
+            .identifier(identifiers)
             .extension(vec![
                 Extension::builder()
                     .url("http://hl7.org/fhir/StructureDefinition/patient-birthPlace".to_string())
@@ -157,6 +484,9 @@ impl TryInto<Patient> for IdRequest {
                     ))
                     .build()?,
             ]);
+        if let Some(gender) = self.idat.gender {
+            builder = builder.gender(gender.into());
+        }
 
         Ok(builder.build()?)
     }
@@ -164,14 +494,33 @@ impl TryInto<Patient> for IdRequest {
 
 impl From<MpiIdentity> for Idat {
     fn from(value: MpiIdentity) -> Self {
+        // latest contact, E-PIX does not guarantee the order of contacts
+        let contact = value
+            .contacts
+            .into_iter()
+            .max_by_key(|c| (c.contact_last_edited, c.contact_id))
+            .unwrap_or_default();
+
         Idat {
             first_name: value.first_name,
             last_name: value.last_name,
             birth_date: value.birth_date,
             birth_place: value.birth_place,
             birth_name: value.mothers_maiden_name,
-            postal_code: value.contacts.zip_code,
-            city: value.contacts.city,
+            gender: value.gender.as_deref().and_then(Gender::from_epix_code),
+            postal_code: contact.zip_code,
+            city: contact.city,
+            street: contact.street,
+            country: contact.country_code,
+            phone: contact.phone,
+            identifiers: value
+                .identifiers
+                .into_iter()
+                .map(|i| Identifier {
+                    system: i.identifier_domain.name,
+                    value: i.value,
+                })
+                .collect(),
         }
     }
 }
@@ -180,7 +529,153 @@ impl From<MpiIdentity> for IdMatch {
     fn from(value: MpiIdentity) -> Self {
         IdMatch {
             idat: value.clone().into(),
+            identity_id: value.identity_id,
             link_id: value.identity_id,
+            possible_match_id: None,
+            probability: None,
+            priority: None,
+            created: None,
+            diff: vec![],
+        }
+    }
+}
+
+impl IdMatch {
+    /// Candidate of a possible match compared to the submitted IDAT
+    pub(crate) fn candidate(submitted: &Idat, value: PossibleMatchResult) -> Self {
+        let idat: Idat = value.matching_identity.identity.clone().into();
+        IdMatch {
+            diff: submitted.diff(&idat),
+            idat,
+            identity_id: value.matching_identity.identity.identity_id,
+            link_id: value.matching_identity.identity.identity_id,
+            possible_match_id: Some(value.link_id),
+            probability: Some(value.probability),
+            priority: Some(value.priority),
+            created: Some(value.possible_match_created),
+        }
+    }
+}
+
+impl Idat {
+    /// Whether both IDAT have the same contact (address and phone)
+    pub(crate) fn same_contact(&self, other: &Idat) -> bool {
+        self.postal_code == other.postal_code
+            && self.city == other.city
+            && self.street == other.street
+            && self.country == other.country
+            && self.phone == other.phone
+    }
+
+    /// Sorted identifiers as `system|value`, independent of their order
+    fn identifiers_text(&self) -> Option<String> {
+        if self.identifiers.is_empty() {
+            return None;
+        }
+        let mut identifiers: Vec<_> = self
+            .identifiers
+            .iter()
+            .map(|i| format!("{}|{}", i.system, i.value))
+            .collect();
+        identifiers.sort();
+        Some(identifiers.join(", "))
+    }
+
+    /// Fields with different values in both IDAT
+    pub(crate) fn diff(&self, other: &Idat) -> Vec<FieldDiff> {
+        let fields = [
+            (
+                "first_name",
+                Some(self.first_name.clone()),
+                Some(other.first_name.clone()),
+            ),
+            (
+                "last_name",
+                Some(self.last_name.clone()),
+                Some(other.last_name.clone()),
+            ),
+            (
+                "birth_date",
+                Some(self.birth_date.to_string()),
+                Some(other.birth_date.to_string()),
+            ),
+            (
+                "birth_place",
+                Some(self.birth_place.clone()),
+                Some(other.birth_place.clone()),
+            ),
+            (
+                "birth_name",
+                self.birth_name.clone(),
+                other.birth_name.clone(),
+            ),
+            (
+                "gender",
+                self.gender.map(|g| g.to_string()),
+                other.gender.map(|g| g.to_string()),
+            ),
+            (
+                "postal_code",
+                Some(self.postal_code.clone()),
+                Some(other.postal_code.clone()),
+            ),
+            ("city", Some(self.city.clone()), Some(other.city.clone())),
+            ("street", self.street.clone(), other.street.clone()),
+            ("country", self.country.clone(), other.country.clone()),
+            ("phone", self.phone.clone(), other.phone.clone()),
+            (
+                "identifiers",
+                self.identifiers_text(),
+                other.identifiers_text(),
+            ),
+        ];
+
+        fields
+            .into_iter()
+            .filter(|(_, submitted, candidate)| submitted != candidate)
+            .map(|(field, submitted, candidate)| FieldDiff {
+                field: field.to_string(),
+                submitted,
+                candidate,
+            })
+            .collect()
+    }
+}
+
+impl OpenMatch {
+    pub(crate) fn new(domain: &str, value: PossibleMatchForDomain) -> Self {
+        OpenMatch {
+            domain: domain.to_string(),
+            link_id: value.link_id,
+            probability: value.probability,
+            priority: value.priority,
+            identities: value
+                .matching_identities
+                .into_iter()
+                .map(|m| m.identity.into())
+                .collect(),
+        }
+    }
+}
+
+impl From<&Domain> for TrialSummary {
+    fn from(value: &Domain) -> Self {
+        TrialSummary {
+            name: value.name.clone(),
+            label: value.label.clone(),
+        }
+    }
+}
+
+impl From<&Domain> for DomainInfo {
+    fn from(value: &Domain) -> Self {
+        DomainInfo {
+            alphabet: value.alphabet.clone(),
+            check_digit_class: value.check_digit_class.clone(),
+            psn_length: value.config.psn_length,
+            prefix: value.config.psn_prefix.clone(),
+            psns_deletable: value.config.psns_deletable,
+            multi_psn: value.config.multi_psn_domain,
         }
     }
 }
@@ -204,9 +699,16 @@ pub(crate) fn parse_date(date: NaiveDate) -> Result<time::Date, ComponentRange>
 
 #[cfg(test)]
 pub(crate) mod tests {
-    use crate::model::Idat;
-    use crate::ttp::epix::model::{IdentityAddress, MpiIdentity};
-    use chrono::NaiveDate;
+    use crate::error::ErrorCode;
+    use crate::model::{BatchResult, Gender, IdMatch, IdRequest, IdResponse, Idat, Identifier};
+    use crate::ttp::epix::model::{
+        IdentifierDomainName, Identity, IdentityAddress, IdentityIdentifier, MatchingIdentity,
+        MpiId, MpiIdentity, PossibleMatchResult,
+    };
+    use chrono::{DateTime, NaiveDate};
+    use fhir_model::r4b::resources::Patient;
+    use serde_json::json;
+    use std::collections::HashMap;
 
     #[test]
     fn test_identity_into_idat() {
@@ -218,6 +720,7 @@ pub(crate) mod tests {
             birth_name: Some("Muster".to_string()),
             postal_code: "35037".to_string(),
             city: "Marburg".to_string(),
+            ..Default::default()
         };
         let identity = MpiIdentity {
             birth_date: NaiveDate::from_ymd_opt(1981, 11, 2).unwrap(),
@@ -225,10 +728,13 @@ pub(crate) mod tests {
             first_name: "Max".to_string(),
             last_name: "Mustermann".to_string(),
             mothers_maiden_name: Some("Muster".to_string()),
-            contacts: IdentityAddress {
+            gender: None,
+            contacts: vec![IdentityAddress {
                 zip_code: "35037".to_string(),
                 city: "Marburg".to_string(),
-            },
+                ..Default::default()
+            }],
+            identifiers: vec![],
             identity_id: 0,
         };
 
@@ -236,4 +742,212 @@ pub(crate) mod tests {
 
         assert_eq!(expected, actual);
     }
+
+    #[test]
+    fn test_identity_latest_contact() {
+        let identity = MpiIdentity {
+            birth_date: NaiveDate::from_ymd_opt(1981, 11, 2).unwrap(),
+            birth_place: "Berlin".to_string(),
+            first_name: "Max".to_string(),
+            last_name: "Mustermann".to_string(),
+            mothers_maiden_name: None,
+            gender: Some("M".to_string()),
+            contacts: vec![
+                IdentityAddress {
+                    zip_code: "35037".to_string(),
+                    city: "Marburg".to_string(),
+                    street: Some("Musterweg 1".to_string()),
+                    country_code: Some("DE".to_string()),
+                    phone: Some("06421 12345".to_string()),
+                    contact_id: Some(2),
+                    contact_last_edited: DateTime::parse_from_rfc3339("2025-11-21T14:16:23+01:00")
+                        .ok(),
+                },
+                IdentityAddress {
+                    zip_code: "10115".to_string(),
+                    city: "Berlin".to_string(),
+                    contact_id: Some(1),
+                    contact_last_edited: DateTime::parse_from_rfc3339("2025-11-19T23:12:53+01:00")
+                        .ok(),
+                    ..Default::default()
+                },
+            ],
+            identifiers: vec![IdentityIdentifier {
+                identifier_domain: IdentifierDomainName {
+                    name: "kvnr".to_string(),
+                },
+                value: "A123456780".to_string(),
+            }],
+            identity_id: 0,
+        };
+
+        let actual: Idat = identity.into();
+
+        assert_eq!(actual.gender, Some(Gender::Male));
+        assert_eq!(actual.postal_code, "35037");
+        assert_eq!(actual.city, "Marburg");
+        assert_eq!(actual.street, Some("Musterweg 1".to_string()));
+        assert_eq!(actual.country, Some("DE".to_string()));
+        assert_eq!(actual.phone, Some("06421 12345".to_string()));
+        assert_eq!(
+            actual.identifiers,
+            vec![Identifier {
+                system: "kvnr".to_string(),
+                value: "A123456780".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_id_request_into_patient() {
+        let request = IdRequest {
+            idat: Idat {
+                first_name: "Max".to_string(),
+                last_name: "Mustermann".to_string(),
+                birth_date: NaiveDate::from_ymd_opt(1981, 11, 2).unwrap(),
+                birth_place: "Berlin".to_string(),
+                gender: Some(Gender::Male),
+                postal_code: "35037".to_string(),
+                city: "Marburg".to_string(),
+                street: Some("Musterweg 1".to_string()),
+                country: Some("DE".to_string()),
+                phone: Some("06421 12345".to_string()),
+                identifiers: vec![Identifier {
+                    system: "kvnr".to_string(),
+                    value: "A123456780".to_string(),
+                }],
+                ..Default::default()
+            },
+            trial: "trial".to_string(),
+            lab: HashMap::new(),
+            link: None,
+            defer: false,
+            source: None,
+        };
+
+        let patient: Patient = request.try_into().unwrap();
+        let actual = serde_json::to_value(patient).unwrap();
+
+        assert_eq!(actual["gender"], "male");
+        assert_eq!(
+            actual["address"],
+            json!([{
+                "line": ["Musterweg 1"],
+                "city": "Marburg",
+                "postalCode": "35037",
+                "country": "DE"
+            }])
+        );
+        assert_eq!(
+            actual["telecom"],
+            json!([{ "system": "phone", "value": "06421 12345" }])
+        );
+        assert_eq!(
+            actual["identifier"],
+            json!([{ "system": "kvnr", "value": "A123456780" }])
+        );
+    }
+
+    #[test]
+    fn test_candidate_diff() {
+        let submitted = Idat {
+            first_name: "Erika".to_string(),
+            last_name: "Musterfrau".to_string(),
+            birth_date: NaiveDate::from_ymd_opt(1972, 1, 1).unwrap(),
+            birth_place: "Berlin".to_string(),
+            birth_name: None,
+            postal_code: "35037".to_string(),
+            city: "Marburg".to_string(),
+            identifiers: vec![Identifier {
+                system: "kvnr".to_string(),
+                value: "A123456780".to_string(),
+            }],
+            ..Default::default()
+        };
+        let possible_match = PossibleMatchResult {
+            link_id: 42,
+            possible_match_created: DateTime::parse_from_rfc3339("2025-11-21T14:16:23.242+01:00")
+                .unwrap(),
+            priority: "OPEN".to_string(),
+            probability: 3.15,
+            matching_identity: MatchingIdentity {
+                identity: MpiIdentity {
+                    birth_date: NaiveDate::from_ymd_opt(1972, 1, 1).unwrap(),
+                    birth_place: "Berlin".to_string(),
+                    first_name: "Erika".to_string(),
+                    last_name: "Mustermann".to_string(),
+                    mothers_maiden_name: Some("Musterfrau".to_string()),
+                    gender: None,
+                    contacts: vec![IdentityAddress {
+                        zip_code: "35037".to_string(),
+                        city: "Marburg".to_string(),
+                        ..Default::default()
+                    }],
+                    identifiers: vec![IdentityIdentifier {
+                        identifier_domain: IdentifierDomainName {
+                            name: "kvnr".to_string(),
+                        },
+                        value: "B123456781".to_string(),
+                    }],
+                    identity_id: 1,
+                },
+                mpi_id: MpiId {
+                    value: "1001000000001".to_string(),
+                },
+            },
+            assigned_identity: Identity { identity_id: 2 },
+        };
+
+        let actual = serde_json::to_value(IdMatch::candidate(&submitted, possible_match)).unwrap();
+
+        assert_eq!(actual["identity_id"], 1);
+        assert_eq!(actual["link_id"], 1);
+        assert_eq!(actual["possible_match_id"], 42);
+        assert_eq!(actual["probability"], 3.15);
+        assert_eq!(actual["priority"], "OPEN");
+        assert_eq!(actual["created"], "2025-11-21T14:16:23.242+01:00");
+        assert_eq!(
+            actual["diff"],
+            json!([
+                { "field": "last_name", "submitted": "Musterfrau", "candidate": "Mustermann" },
+                { "field": "birth_name", "submitted": null, "candidate": "Musterfrau" },
+                { "field": "identifiers", "submitted": "kvnr|A123456780", "candidate": "kvnr|B123456781" }
+            ])
+        );
+    }
+
+    #[test]
+    fn test_batch_result_serde() {
+        let results = vec![
+            BatchResult::Created(IdResponse {
+                participant: "VYMGJ9TUMDHFPL14".to_string(),
+                lab: HashMap::from([("lab".to_string(), vec!["CCRPJTW1R8WU6W3P".to_string()])]),
+                idat_updated: false,
+            }),
+            BatchResult::Error {
+                code: 502,
+                error: ErrorCode::TtpUnavailable,
+                message: "gPAS unavailable".to_string(),
+            },
+        ];
+
+        let actual = serde_json::to_value(results).unwrap();
+
+        assert_eq!(
+            actual,
+            json!([
+                {
+                    "status": "created",
+                    "participant": "VYMGJ9TUMDHFPL14",
+                    "lab": { "lab": ["CCRPJTW1R8WU6W3P"] }
+                },
+                {
+                    "status": "error",
+                    "code": 502,
+                    "error": "ttp_unavailable",
+                    "message": "gPAS unavailable"
+                }
+            ])
+        );
+    }
 }
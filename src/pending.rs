@@ -0,0 +1,226 @@
+use crate::config::Pending;
+use anyhow::anyhow;
+use chrono::Utc;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Possible match which was kept in E-PIX to be resolved later
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct PendingMatch {
+    pub(crate) identity_id: u32,
+    pub(crate) mpi: String,
+    pub(crate) trial: String,
+    pub(crate) lab: HashMap<String, u32>,
+    /// E-PIX data source of the request
+    pub(crate) source: Option<String>,
+}
+
+/// Deferred possible matches by resolution token, in a SQLite file or in memory. Matches in
+/// memory are lost on restart, which leaves their identities as open possible matches.
+#[derive(Clone)]
+pub(crate) struct PendingMatches {
+    ttl: Duration,
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl Default for PendingMatches {
+    fn default() -> Self {
+        PendingMatches::new(&Pending::default()).expect("in-memory database")
+    }
+}
+
+impl PendingMatches {
+    pub(crate) fn new(config: &Pending) -> anyhow::Result<Self> {
+        let conn = Connection::open(config.path.as_deref().unwrap_or(":memory:"))?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS pending (
+                token   TEXT PRIMARY KEY,
+                created INTEGER NOT NULL,
+                record  TEXT NOT NULL
+            )",
+            (),
+        )?;
+
+        Ok(PendingMatches {
+            ttl: Duration::from_secs(config.ttl),
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// Run a query on a blocking thread, so the runtime is not blocked by file I/O
+    async fn with_connection<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&Connection) -> anyhow::Result<T> + Send + 'static,
+    ) -> anyhow::Result<T> {
+        let conn = Arc::clone(&self.conn);
+        tokio::task::spawn_blocking(move || {
+            let conn = conn
+                .lock()
+                .map_err(|_| anyhow!("Pending match store is poisoned"))?;
+            f(&conn)
+        })
+        .await?
+    }
+
+    /// Creation time (in milliseconds) before which matches are expired
+    fn expiry(&self) -> i64 {
+        Utc::now().timestamp_millis() - self.ttl.as_millis() as i64
+    }
+
+    /// Store a pending match and return its resolution token
+    pub(crate) async fn insert(&self, pending: PendingMatch) -> anyhow::Result<String> {
+        let token = Uuid::new_v4().to_string();
+        let record = serde_json::to_string(&pending)?;
+
+        let key = token.clone();
+        self.with_connection(move |conn| {
+            conn.execute(
+                "INSERT INTO pending (token, created, record) VALUES (?1, ?2, ?3)",
+                params![key, Utc::now().timestamp_millis(), record],
+            )?;
+            Ok(())
+        })
+        .await?;
+
+        Ok(token)
+    }
+
+    /// Remove a pending match for resolution, so concurrent requests cannot resolve it twice.
+    /// Returns its creation time to restore it. Expired matches are left to
+    /// [`PendingMatches::expired`].
+    pub(crate) async fn take(&self, token: &str) -> anyhow::Result<Option<(i64, PendingMatch)>> {
+        let (token, expiry) = (token.to_string(), self.expiry());
+        self.with_connection(move |conn| {
+            let entry = conn
+                .query_row(
+                    "SELECT created, record FROM pending WHERE token = ?1 AND created > ?2",
+                    params![token, expiry],
+                    |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)),
+                )
+                .optional()?;
+
+            match entry {
+                Some((created, record)) => {
+                    conn.execute("DELETE FROM pending WHERE token = ?1", params![token])?;
+                    Ok(Some((created, serde_json::from_str(&record)?)))
+                }
+                None => Ok(None),
+            }
+        })
+        .await
+    }
+
+    /// Put back a pending match whose resolution failed. It keeps its creation time, so retries
+    /// do not extend its expiry.
+    pub(crate) async fn restore(
+        &self,
+        token: String,
+        created: i64,
+        pending: PendingMatch,
+    ) -> anyhow::Result<()> {
+        let record = serde_json::to_string(&pending)?;
+        self.with_connection(move |conn| {
+            conn.execute(
+                "INSERT INTO pending (token, created, record) VALUES (?1, ?2, ?3)",
+                params![token, created, record],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Remove and return expired matches, whose kept identities must be cleaned up
+    pub(crate) async fn expired(&self) -> anyhow::Result<Vec<PendingMatch>> {
+        let expiry = self.expiry();
+        self.with_connection(move |conn| {
+            let records = conn
+                .prepare("SELECT record FROM pending WHERE created <= ?1")?
+                .query_map(params![expiry], |row| row.get::<_, String>(0))?
+                .collect::<Result<Vec<_>, _>>()?;
+            conn.execute("DELETE FROM pending WHERE created <= ?1", params![expiry])?;
+
+            records
+                .iter()
+                .map(|record| Ok(serde_json::from_str(record)?))
+                .collect()
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Pending;
+    use crate::pending::{PendingMatch, PendingMatches};
+    use std::collections::HashMap;
+
+    fn pending_match() -> PendingMatch {
+        PendingMatch {
+            identity_id: 2,
+            mpi: "1001000000002".to_string(),
+            trial: "trial".to_string(),
+            lab: HashMap::from([("lab".to_string(), 1)]),
+            source: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_insert_and_take() {
+        let pending = PendingMatches::default();
+
+        let token = pending.insert(pending_match()).await.unwrap();
+        let (created, taken) = pending.take(&token).await.unwrap().unwrap();
+        assert_eq!(taken, pending_match());
+        // taken only once
+        assert_eq!(pending.take(&token).await.unwrap(), None);
+
+        // restored with the original creation time
+        pending
+            .restore(token.clone(), created, pending_match())
+            .await
+            .unwrap();
+        assert_eq!(
+            pending.take(&token).await.unwrap(),
+            Some((created, pending_match()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_expired_token() {
+        let pending = PendingMatches::new(&Pending { ttl: 0, path: None }).unwrap();
+
+        let token = pending.insert(pending_match()).await.unwrap();
+
+        assert_eq!(pending.take(&token).await.unwrap(), None);
+        // kept for cleanup
+        assert_eq!(pending.expired().await.unwrap(), vec![pending_match()]);
+        assert!(pending.expired().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_persisted() {
+        let path = std::env::temp_dir().join(format!("pending-{}.db", uuid::Uuid::new_v4()));
+        let config = Pending {
+            path: Some(path.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+
+        let token = PendingMatches::new(&config)
+            .unwrap()
+            .insert(pending_match())
+            .await
+            .unwrap();
+        // still resolvable after a restart
+        let restarted = PendingMatches::new(&config).unwrap();
+        assert_eq!(
+            restarted.take(&token).await.unwrap().map(|(_, p)| p),
+            Some(pending_match())
+        );
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,191 @@
+use crate::error::ApiError;
+use crate::ttp::client::TtpClient;
+use log::{error, info};
+use std::fmt;
+
+/// Completed step of the create workflow which can be undone
+#[derive(Debug, PartialEq)]
+pub(crate) enum Step {
+    /// Newly created E-PIX identity
+    Identity(u32),
+    /// Pseudonyms of a new MPI in a gPAS domain of the trial
+    Pseudonyms { domain: String, mpi: String },
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Step::Identity(id) => write!(f, "E-PIX identity {id}"),
+            Step::Pseudonyms { domain, .. } => write!(f, "gPAS pseudonyms in domain {domain}"),
+        }
+    }
+}
+
+/// Records completed steps to undo them in reverse order on failure. Compensation continues
+/// after a failed step, so as little as possible is left behind, and all failed steps are
+/// reported.
+pub(crate) struct Saga<'a> {
+    client: &'a TtpClient,
+    steps: Vec<Step>,
+}
+
+impl<'a> Saga<'a> {
+    pub(crate) fn new(client: &'a TtpClient) -> Self {
+        Saga {
+            client,
+            steps: Vec::new(),
+        }
+    }
+
+    /// Record a step after it succeeded
+    pub(crate) fn record(&mut self, step: Step) {
+        self.steps.push(step);
+    }
+
+    /// Run a step of the workflow and roll back all recorded steps if it fails
+    pub(crate) async fn run<T, E: Into<ApiError>>(
+        &mut self,
+        step: impl Future<Output = Result<T, E>>,
+    ) -> Result<T, ApiError> {
+        match step.await {
+            Ok(res) => Ok(res),
+            Err(e) => Err(self.rollback(e.into()).await),
+        }
+    }
+
+    async fn rollback(&mut self, err: ApiError) -> ApiError {
+        if self.steps.is_empty() {
+            return err;
+        }
+
+        let mut undone = Vec::new();
+        let mut failed = Vec::new();
+        while let Some(step) = self.steps.pop() {
+            let res = match &step {
+                Step::Identity(id) => self.client.delete_identity(*id).await,
+                Step::Pseudonyms { domain, mpi } => {
+                    self.client.delete_entry(domain.clone(), mpi.clone()).await
+                }
+            };
+            match res {
+                Ok(_) => {
+                    info!("Rolled back {step}");
+                    undone.push(step.to_string());
+                }
+                Err(e) => {
+                    error!("Failed to roll back {step}: {e}");
+                    failed.push(step.to_string());
+                }
+            }
+        }
+
+        let mut note = format!("Rolled back: [{}]", undone.join(", "));
+        if !failed.is_empty() {
+            note.push_str(&format!(". Failed to roll back: [{}]", failed.join(", ")));
+        }
+
+        err.with_note(&note)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::saga::{Saga, Step};
+    use crate::ttp::client::tests::setup_config;
+    use crate::ttp::client::TtpClient;
+    use anyhow::anyhow;
+    use httpmock::prelude::*;
+    use reqwest::StatusCode;
+
+    fn setup_saga(client: &TtpClient) -> Saga<'_> {
+        let mut saga = Saga::new(client);
+        saga.record(Step::Identity(5));
+        saga.record(Step::Pseudonyms {
+            domain: "trial".to_string(),
+            mpi: "1001000000011".to_string(),
+        });
+        saga.record(Step::Pseudonyms {
+            domain: "trial_lab".to_string(),
+            mpi: "1001000000011".to_string(),
+        });
+        saga
+    }
+
+    #[tokio::test]
+    async fn test_rollback() {
+        let server = MockServer::start();
+        let delete_psn_mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/gpas/gpasService")
+                .body_includes("<value>1001000000011</value>");
+            then.status(200);
+        });
+        let delete_identity_mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/epix/epixService")
+                .body_includes("<identityId>5</identityId>");
+            then.status(200);
+        });
+
+        let config = setup_config(server.base_url());
+        let client = TtpClient::new(&config.ttp, &config.trials).await.unwrap();
+        let mut saga = setup_saga(&client);
+
+        // act
+        let err = saga
+            .run(async { Err::<(), _>(anyhow!("gPAS failed")) })
+            .await
+            .err()
+            .unwrap();
+
+        // all steps are undone
+        delete_psn_mock.assert_calls(2);
+        delete_identity_mock.assert_calls(2);
+        assert_eq!(err.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(
+            err.detail(),
+            "gPAS failed. Rolled back: [gPAS pseudonyms in domain trial_lab, gPAS pseudonyms in domain trial, E-PIX identity 5]"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rollback_continues_on_failure() {
+        let server = MockServer::start();
+        let delete_lab_mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/gpas/gpasService")
+                .body_includes("<domainName>trial_lab</domainName>");
+            then.status(200);
+        });
+        let delete_trial_mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/gpas/gpasService")
+                .body_includes("<domainName>trial</domainName>");
+            then.status(500).body("not deletable");
+        });
+        let delete_identity_mock = server.mock(|when, then| {
+            when.method(POST).path("/epix/epixService");
+            then.status(200);
+        });
+
+        let config = setup_config(server.base_url());
+        let client = TtpClient::new(&config.ttp, &config.trials).await.unwrap();
+        let mut saga = setup_saga(&client);
+
+        // act
+        let err = saga
+            .run(async { Err::<(), _>(anyhow!("gPAS failed")) })
+            .await
+            .err()
+            .unwrap();
+
+        // remaining steps are still undone
+        delete_lab_mock.assert();
+        delete_trial_mock.assert();
+        delete_identity_mock.assert_calls(2);
+        assert_eq!(
+            err.detail(),
+            "gPAS failed. Rolled back: [gPAS pseudonyms in domain trial_lab, E-PIX identity 5]. Failed to roll back: [gPAS pseudonyms in domain trial]"
+        );
+    }
+}
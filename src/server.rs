@@ -1,8 +1,14 @@
+use crate::access::AccessRules;
 use crate::api;
-use crate::config::AppConfig;
+use crate::audit::AuditLog;
+use crate::config::{AppConfig, Oidc, TokenValidation};
+use crate::error;
+use crate::idempotency::IdempotencyStore;
 use crate::model;
+use crate::pending::PendingMatches;
 use crate::ttp::client::TtpClient;
-use auth::oauth::Oidc as OidcAuth;
+use anyhow::anyhow;
+use auth::oauth::{JwtValidation, Oidc as OidcAuth};
 use axum::extract::State;
 use axum::response::IntoResponse;
 use axum::routing::get;
@@ -13,18 +19,33 @@ use serde::Serialize;
 use shadow_rs::shadow;
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 use tower_http::trace::TraceLayer;
 use tracing_subscriber::EnvFilter;
 use utoipa::openapi::security::{ClientCredentials, Flow, OAuth2, Scopes, SecurityScheme};
 use utoipa::{Modify, OpenApi};
 use utoipa_swagger_ui::{Config, SwaggerUi};
 
+/// Interval to clean up expired deferred matches
+const PENDING_CLEANUP_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
 #[derive(Clone)]
 pub(crate) struct ApiContext {
     pub(crate) client: TtpClient,
+    pub(crate) pending: PendingMatches,
+    pub(crate) idempotency: IdempotencyStore,
+    pub(crate) access: AccessRules,
+    pub(crate) audit: AuditLog,
     build: ApiBuild,
 }
 
+#[derive(Clone)]
+struct AuthContext {
+    oidc: Arc<OidcAuth>,
+    idat_scope: String,
+    admin_scope: String,
+}
+
 #[derive(utoipa::ToSchema, Serialize)]
 struct ApiStatus {
     name: String,
@@ -74,23 +95,34 @@ pub(crate) async fn serve(config: AppConfig, build: ApiBuild) -> anyhow::Result<
         .init();
 
     // TTP client
-    let client = TtpClient::new(&config.ttp).await?;
+    let client = TtpClient::new(&config.ttp, &config.trials).await?;
     client.test_connection().await?;
     client.setup_domains().await?;
+    client.check_deletable().await?;
+
+    let oidc = config.auth.and_then(|auth| auth.oidc);
 
     // api state
-    let state = Arc::new(ApiContext { client, build });
+    let state = Arc::new(ApiContext {
+        client,
+        pending: PendingMatches::new(&config.pending)?,
+        idempotency: IdempotencyStore::new(&config.idempotency)?,
+        access: oidc.as_ref().map(AccessRules::new).unwrap_or_default(),
+        audit: AuditLog::new(&config.audit)?,
+        build,
+    });
 
     // auth state
-    let auth_state = match config
-        .auth
-        .and_then(|auth| auth.oidc)
-        .map(|o| OidcAuth::new(o.client_id, o.issuer_url))
-    {
+    let auth_state = match oidc {
         None => None,
-        Some(res) => Some(Arc::new(res.await?)),
+        Some(o) => Some(AuthContext {
+            oidc: Arc::new(oidc_auth(&o).await?),
+            idat_scope: o.idat_scope,
+            admin_scope: o.admin_scope,
+        }),
     };
 
+    tokio::spawn(cleanup_pending(Arc::clone(&state)));
     let router = build_router(state, auth_state);
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await?;
@@ -103,7 +135,65 @@ pub(crate) async fn serve(config: AppConfig, build: ApiBuild) -> anyhow::Result<
     .map_err(|e| e.into())
 }
 
-fn build_router(api_state: Arc<ApiContext>, auth_state: Option<Arc<OidcAuth>>) -> Router {
+/// Delete the kept E-PIX identities of expired deferred matches
+async fn cleanup_pending(ctx: Arc<ApiContext>) {
+    let mut interval = tokio::time::interval(PENDING_CLEANUP_INTERVAL);
+    loop {
+        interval.tick().await;
+        let expired = match ctx.pending.expired().await {
+            Ok(expired) => expired,
+            Err(e) => {
+                log::error!("Failed to get expired possible matches: {e}");
+                continue;
+            }
+        };
+        for pending in expired {
+            match ctx.client.delete_identity(pending.identity_id).await {
+                Ok(_) => info!(
+                    "Deleted identity {} of an expired possible match in trial {}",
+                    pending.identity_id, pending.trial
+                ),
+                // still listed as open possible match
+                Err(e) => log::error!(
+                    "Failed to delete identity {} of an expired possible match: {e}",
+                    pending.identity_id
+                ),
+            }
+        }
+    }
+}
+
+async fn oidc_auth(oidc: &Oidc) -> anyhow::Result<OidcAuth> {
+    let (client_id, issuer_url) = (oidc.client_id.clone(), oidc.issuer_url.clone());
+    let validation = JwtValidation {
+        audiences: oidc.audiences.clone(),
+        leeway: oidc.leeway,
+        required_claims: oidc.required_claims.clone(),
+        authorized_parties: oidc.authorized_parties.clone(),
+    };
+
+    Ok(match oidc.token_validation {
+        TokenValidation::Jwt => {
+            OidcAuth::with_jwt_validation(client_id, issuer_url, validation).await?
+        }
+        TokenValidation::Introspection => {
+            let client_secret = oidc.client_secret.clone().ok_or(anyhow!(
+                "auth.oidc.client_secret is required for token introspection"
+            ))?;
+            info!("Validating access tokens by introspection");
+            OidcAuth::with_introspection(
+                client_id,
+                client_secret,
+                issuer_url,
+                Duration::from_secs(oidc.introspection_cache_ttl),
+                validation,
+            )
+            .await?
+        }
+    })
+}
+
+fn build_router(api_state: Arc<ApiContext>, auth_state: Option<AuthContext>) -> Router {
     api_route(auth_state)
         .route("/status", get(status))
         .merge(
@@ -115,14 +205,29 @@ fn build_router(api_state: Arc<ApiContext>, auth_state: Option<Arc<OidcAuth>>) -
         .layer(TraceLayer::new_for_http())
 }
 
-fn api_route(auth_state: Option<Arc<OidcAuth>>) -> Router<Arc<ApiContext>> {
+fn api_route(auth_state: Option<AuthContext>) -> Router<Arc<ApiContext>> {
     if let Some(auth) = auth_state {
-        api::router().layer(middleware::from_fn_with_state(
-            auth,
-            auth::oauth::auth_middleware,
-        ))
+        api::router()
+            .merge(
+                api::idat_router().route_layer(middleware::from_fn_with_state(
+                    auth.idat_scope,
+                    auth::oauth::scope_middleware,
+                )),
+            )
+            .merge(
+                api::admin_router().route_layer(middleware::from_fn_with_state(
+                    auth.admin_scope,
+                    auth::oauth::scope_middleware,
+                )),
+            )
+            .layer(middleware::from_fn_with_state(
+                auth.oidc,
+                auth::oauth::auth_middleware,
+            ))
     } else {
         api::router()
+            .merge(api::idat_router())
+            .merge(api::admin_router())
     }
 }
 
@@ -132,6 +237,19 @@ fn api_route(auth_state: Option<Arc<OidcAuth>>) -> Router<Arc<ApiContext>> {
         status,
         api::create,
         api::read,
+        api::add_lab,
+        api::read_lab,
+        api::create_batch,
+        api::list_matches,
+        api::resolve_pending,
+        api::resolve_open,
+        api::update_participant,
+        api::delete_participant,
+        api::read_idat,
+        api::list_trials,
+        api::read_trial,
+        api::create_trial,
+        api::audit,
     ),
     components(schemas(
         model::IdRequest,
@@ -139,6 +257,23 @@ fn api_route(auth_state: Option<Arc<OidcAuth>>) -> Router<Arc<ApiContext>> {
         model::Idat,
         model::PromptResponse,
         model::Link,
+        model::LabRequest,
+        model::BatchResponse,
+        model::BatchResult,
+        model::OpenMatch,
+        model::IdentityAction,
+        error::ProblemDetails,
+        error::ErrorCode,
+        model::TrialRequest,
+        model::TrialSummary,
+        model::TrialResponse,
+        model::LabDomain,
+        model::DomainInfo,
+        model::AuditResponse,
+        model::AuditEntry,
+        model::AuditEvent,
+        model::AuditAction,
+        model::AuditLink,
     )),
     modifiers(&SecurityAddon),
     tags((name = "Pseudonym management"))
@@ -163,23 +298,38 @@ impl Modify for SecurityAddon {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::idempotency::{self, StoredResponse};
+    use crate::ttp::client::tests::setup_config;
     use axum_test::TestServer;
     use serde_json::json;
     use std::sync::Arc;
 
-    #[tokio::test]
-    async fn status_test() {
-        let config = AppConfig::default();
-        let api_build = ApiBuild {
+    fn test_build() -> ApiBuild {
+        ApiBuild {
             version: "1.0.0".to_string(),
             mode: "debug".to_string(),
             time: "2025-12-06 20:12:45 +01:00".to_string(),
-        };
+        }
+    }
+
+    /// Api state with an unconfigured TTP client and in-memory stores
+    async fn test_context(config: &AppConfig) -> ApiContext {
+        ApiContext {
+            client: TtpClient::new(&config.ttp, &config.trials).await.unwrap(),
+            pending: PendingMatches::default(),
+            idempotency: IdempotencyStore::default(),
+            access: AccessRules::default(),
+            audit: AuditLog::new(&crate::audit::tests::setup_config()).unwrap(),
+            build: test_build(),
+        }
+    }
+
+    #[tokio::test]
+    async fn status_test() {
+        let config = AppConfig::default();
+        let api_build = test_build();
         {
-            let state = Arc::new(ApiContext {
-                client: TtpClient::new(&config.ttp).await.unwrap(),
-                build: api_build.clone(),
-            });
+            let state = Arc::new(test_context(&config).await);
 
             // test server
             let router = build_router(state.clone(), None);
@@ -197,4 +347,686 @@ mod tests {
             }));
         }
     }
+
+    #[tokio::test]
+    async fn idempotency_test() {
+        use crate::audit::Principal;
+        use auth::oauth::Claims;
+        use axum::Extension;
+
+        let config = AppConfig::default();
+        let state = Arc::new(test_context(&config).await);
+        let server = TestServer::new(build_router(state.clone(), None)).unwrap();
+
+        let body = json!({
+            "idat": {
+                "first_name": "Max",
+                "last_name": "Mustermann",
+                "birth_date": "1970-01-01",
+                "birth_place": "Berlin",
+                "postal_code": "10115",
+                "city": "Berlin"
+            },
+            "trial": "trial",
+            "lab": {}
+        });
+        let response = json!({ "participant": "psn", "lab": {} });
+
+        // response of a previous request
+        let hash = idempotency::hash(body.to_string().as_bytes());
+        let key = idempotency::scoped_key(&Principal::from(&None), "key");
+        state.idempotency.begin(&key, &hash).await.unwrap();
+        state
+            .idempotency
+            .complete(
+                &key,
+                StoredResponse {
+                    status: 200,
+                    body: response.to_string(),
+                },
+            )
+            .await
+            .unwrap();
+
+        // same body is replayed
+        let replayed = server
+            .post("/api/pseudonyms")
+            .add_header("Idempotency-Key", "key")
+            .bytes(body.to_string().into())
+            .await;
+        replayed.assert_status_ok();
+        replayed.assert_header("Idempotent-Replayed", "true");
+        replayed.assert_json(&response);
+
+        // different body is rejected
+        let mut other = body.clone();
+        other["trial"] = json!("other");
+        let conflict = server
+            .post("/api/pseudonyms")
+            .add_header("Idempotency-Key", "key")
+            .json(&other)
+            .await;
+        conflict.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            conflict.json::<serde_json::Value>()["code"],
+            "idempotency_key_reused"
+        );
+        assert_eq!(conflict.header("content-type"), "application/problem+json");
+
+        // keys of other principals are not replayed
+        let claims = Claims {
+            sub: "other".to_string(),
+            ..Default::default()
+        };
+        let server =
+            TestServer::new(build_router(state.clone(), None).layer(Extension(claims))).unwrap();
+        let other = server
+            .post("/api/pseudonyms")
+            .add_header("Idempotency-Key", "key")
+            .bytes(body.to_string().into())
+            .await;
+        assert_ne!(other.status_code(), StatusCode::OK);
+        assert!(other.maybe_header("Idempotent-Replayed").is_none());
+    }
+
+    #[tokio::test]
+    async fn strict_unknown_trial_test() {
+        use httpmock::prelude::*;
+
+        let unknown_domain = r#"<soap:Envelope xmlns:soap="http://schemas.xmlsoap.org/soap/envelope/">
+    <soap:Body>
+        <soap:Fault>
+            <faultcode>soap:Server</faultcode>
+            <faultstring>domain trial not found</faultstring>
+            <detail>
+                <ns1:UnknownDomainException xmlns:ns1="http://psn.ttp.ganimed.icmvc.emau.org/"/>
+            </detail>
+        </soap:Fault>
+    </soap:Body>
+</soap:Envelope>"#;
+
+        let server = MockServer::start();
+        let domain_mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/gpas/DomainService")
+                .body_includes("ns2:getDomain");
+            then.status(500).body(unknown_domain);
+        });
+        let epix_mock = server.mock(|when, then| {
+            when.path_includes("/epix");
+            then.status(500);
+        });
+
+        let mut config = setup_config(server.base_url());
+        config.ttp.gpas.strict = true;
+        let state = Arc::new(test_context(&config).await);
+        let server = TestServer::new(build_router(state, None)).unwrap();
+
+        // send request
+        let response = server
+            .post("/api/pseudonyms")
+            .json(&json!({
+                "idat": {
+                    "first_name": "Max",
+                    "last_name": "Mustermann",
+                    "birth_date": "1970-01-01",
+                    "birth_place": "Berlin",
+                    "postal_code": "10115",
+                    "city": "Berlin"
+                },
+                "trial": "trial",
+                "lab": { "lab": 1 }
+            }))
+            .await;
+
+        // rejected before E-PIX is called
+        response.assert_status_bad_request();
+        domain_mock.assert();
+        epix_mock.assert_calls(0);
+    }
+
+    /// E-PIX $addPatient result of a person with the given identity
+    fn add_patient_response(status: &str, mpi: &str, identity_id: u32) -> serde_json::Value {
+        json!({
+            "resourceType": "Parameters",
+            "parameter": [{
+                "name": "matchResult",
+                "part": [
+                    {
+                        "name": "matchStatus",
+                        "valueCoding": {
+                            "system": "https://ths-greifswald.de/fhir/CodeSystem/epix/MatchStatus",
+                            "code": status
+                        }
+                    },
+                    {
+                        "name": "mpiPerson",
+                        "resource": {
+                            "resourceType": "Person",
+                            "identifier": [{
+                                "system": "https://ths-greifswald.de/fhir/epix/identifier/MPI",
+                                "value": mpi
+                            }]
+                        }
+                    },
+                    {
+                        "name": "identity",
+                        "resource": { "resourceType": "Patient", "id": identity_id.to_string() }
+                    }
+                ]
+            }]
+        })
+    }
+
+    #[tokio::test]
+    async fn create_match_status_test() {
+        use crate::ttp::client::tests::POSSIBLE_MATCHES_RESPONSE;
+        use httpmock::prelude::*;
+
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(POST).path("/gpas/DomainService");
+            then.status(200);
+        });
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/ttp-fhir/fhir/gpas/$pseudonymizeAllowCreate");
+            then.status(200).json_body(json!({
+                "resourceType": "Parameters",
+                "parameter": [{
+                    "name": "pseudonym",
+                    "part": [{ "name": "pseudonym", "valueIdentifier": { "value": "PSN" } }]
+                }]
+            }));
+        });
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/ttp-fhir/fhir/epix/$addPatient")
+                .body_includes("Mustermann");
+            then.status(200).json_body(add_patient_response(
+                "PERFECT_MATCH_WITH_UPDATE",
+                "1001000000011",
+                1,
+            ));
+        });
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/ttp-fhir/fhir/epix/$addPatient")
+                .body_includes("Muster\"");
+            then.status(200)
+                .json_body(add_patient_response("MULTIPLE_MATCH", "1001000000073", 53));
+        });
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/ttp-fhir/fhir/epix/$addPatient")
+                .body_includes("Musterfrau");
+            then.status(200)
+                .json_body(add_patient_response("MULTIPLE_MATCH", "1001000000011", 1));
+        });
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/epix/epixService")
+                .body_includes("getPossibleMatchesForPerson");
+            then.status(200).body(POSSIBLE_MATCHES_RESPONSE);
+        });
+        let delete_mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/epix/epixService")
+                .body_includes("<identityId>53</identityId>");
+            then.status(200);
+        });
+        let delete_other_mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/epix/epixService")
+                .body_includes("<identityId>1</identityId>");
+            then.status(200);
+        });
+
+        let config = setup_config(server.base_url());
+        let state = Arc::new(test_context(&config).await);
+        let server = TestServer::new(build_router(state, None)).unwrap();
+        let request = |last_name: &str| {
+            json!({
+                "idat": {
+                    "first_name": "Max",
+                    "last_name": last_name,
+                    "birth_date": "1972-01-01",
+                    "birth_place": "Musterstadt",
+                    "postal_code": "35037",
+                    "city": "Marburg"
+                },
+                "trial": "trial",
+                "lab": {}
+            })
+        };
+
+        // updated IDAT of a match is reported
+        let response = server
+            .post("/api/pseudonyms")
+            .json(&request("Mustermann"))
+            .await;
+        response.assert_status_ok();
+        response.assert_json(&json!({ "participant": "PSN", "lab": {}, "idat_updated": true }));
+
+        // all candidates of multiple matches are prompted and the new identity is deleted
+        let response = server
+            .post("/api/pseudonyms")
+            .json(&request("Muster"))
+            .await;
+        response.assert_status(StatusCode::CONFLICT);
+        let prompt = response.json::<serde_json::Value>();
+        assert!(prompt.get("unlinked").is_none());
+        assert_eq!(prompt["matches"][0]["identity_id"], 1);
+        assert_eq!(prompt["matches"][0]["link_id"], 1);
+        assert_eq!(prompt["matches"][0]["possible_match_id"], 62);
+        delete_mock.assert_calls(2);
+
+        // identities which are not linked to the candidates are kept
+        let response = server
+            .post("/api/pseudonyms")
+            .json(&request("Musterfrau"))
+            .await;
+        response.assert_status(StatusCode::CONFLICT);
+        let prompt = response.json::<serde_json::Value>();
+        assert_eq!(prompt["unlinked"], true);
+        assert_eq!(prompt["matches"][0]["identity_id"], 1);
+
+        // and cannot be resolved or deferred
+        let mut resolved = request("Musterfrau");
+        resolved["link"] = json!({ "id": 1, "merge": true });
+        resolved["defer"] = json!(true);
+        let response = server.post("/api/pseudonyms").json(&resolved).await;
+        response.assert_status(StatusCode::CONFLICT);
+        let prompt = response.json::<serde_json::Value>();
+        assert_eq!(prompt["unlinked"], true);
+        assert!(prompt.get("token").is_none());
+        delete_other_mock.assert_calls(0);
+    }
+
+    #[tokio::test]
+    async fn batch_size_test() {
+        let config = AppConfig::default();
+        let state = Arc::new(test_context(&config).await);
+        let server = TestServer::new(build_router(state, None)).unwrap();
+        let request = json!({
+            "idat": {
+                "first_name": "Max",
+                "last_name": "Mustermann",
+                "birth_date": "1970-01-01",
+                "birth_place": "Berlin",
+                "postal_code": "10115",
+                "city": "Berlin"
+            },
+            "trial": "trial",
+            "lab": {}
+        });
+
+        // more participants than permits are all processed
+        let response = server
+            .post("/api/pseudonyms/batch")
+            .json(&vec![request.clone(); 10])
+            .await;
+        response.assert_status_ok();
+        let results = response.json::<serde_json::Value>();
+        assert_eq!(results["results"].as_array().unwrap().len(), 10);
+
+        // too many participants are rejected
+        let response = server
+            .post("/api/pseudonyms/batch")
+            .json(&vec![request; 1001])
+            .await;
+        response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            response.json::<serde_json::Value>()["code"],
+            "validation_failed"
+        );
+    }
+
+    #[tokio::test]
+    async fn unknown_psn_test() {
+        use httpmock::prelude::*;
+
+        let server = MockServer::start();
+        let identify_mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/ttp-fhir/fhir/gpas/$dePseudonymize");
+            then.status(200).json_body(json!({
+                "resourceType": "Parameters",
+                "parameter": [{
+                    "name": "error",
+                    "part": [{
+                        "name": "error-code",
+                        "valueCoding": { "code": "not-found", "display": "Not Found" }
+                    }]
+                }]
+            }));
+        });
+
+        let config = setup_config(server.base_url());
+        let state = Arc::new(test_context(&config).await);
+        let server = TestServer::new(build_router(state, None)).unwrap();
+        let idat = json!({
+            "first_name": "Max",
+            "last_name": "Mustermann",
+            "birth_date": "1970-01-01",
+            "birth_place": "Berlin",
+            "postal_code": "10115",
+            "city": "Berlin"
+        });
+
+        let responses = [
+            server.get("/api/pseudonyms/trial/psn").await,
+            server.get("/api/pseudonyms/trial/lab/lab/psn").await,
+            server
+                .post("/api/pseudonyms/trial/psn/lab")
+                .json(&json!({ "lab": { "lab": 1 } }))
+                .await,
+            server.put("/api/participants/trial/psn").json(&idat).await,
+            server.delete("/api/participants/trial/psn").await,
+            server.get("/api/participants/trial/psn/idat").await,
+        ];
+
+        // unknown psns are not found
+        for response in responses {
+            response.assert_status_not_found();
+            assert_eq!(response.header("content-type"), "application/problem+json");
+            assert_eq!(response.json::<serde_json::Value>()["code"], "not_found");
+        }
+        identify_mock.assert_calls(6);
+    }
+
+    #[tokio::test]
+    async fn malformed_body_test() {
+        let config = AppConfig::default();
+        let state = Arc::new(test_context(&config).await);
+        let server = TestServer::new(build_router(state, None)).unwrap();
+
+        // syntax errors
+        let response = server
+            .put("/api/participants/trial/psn")
+            .content_type("application/json")
+            .bytes("{\"first_name\":".into())
+            .await;
+        response.assert_status_bad_request();
+        assert_eq!(response.header("content-type"), "application/problem+json");
+        assert_eq!(response.json::<serde_json::Value>()["code"], "bad_request");
+
+        // missing fields
+        let response = server
+            .post("/api/matches/1")
+            .json(&json!({ "merge": true }))
+            .await;
+        response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(response.header("content-type"), "application/problem+json");
+        assert_eq!(
+            response.json::<serde_json::Value>()["code"],
+            "validation_failed"
+        );
+    }
+
+    #[tokio::test]
+    async fn resolve_routes_test() {
+        let config = AppConfig::default();
+        let state = Arc::new(test_context(&config).await);
+        let server = TestServer::new(build_router(state, None)).unwrap();
+        let link = json!({ "id": 1, "merge": true });
+
+        // unknown token
+        let response = server
+            .post("/api/matches/pending/1c8a4e2e-2f0b-4f53-9d5e-0b7c3f6b1a2d")
+            .json(&link)
+            .await;
+        response.assert_status_not_found();
+
+        // link ids are numeric
+        let response = server.post("/api/matches/token").json(&link).await;
+        response.assert_status_bad_request();
+    }
+
+    #[tokio::test]
+    async fn access_rules_test() {
+        use crate::config::{Oidc, TrialAccess};
+        use auth::oauth::{Access, Claims};
+        use axum::Extension;
+        use std::collections::HashMap;
+
+        let config = AppConfig::default();
+        let state = Arc::new(ApiContext {
+            access: AccessRules::new(&Oidc {
+                steward_role: Some("steward".to_string()),
+                access: HashMap::from([(
+                    "trial".to_string(),
+                    TrialAccess {
+                        grants: vec![],
+                        labs: HashMap::from([("lab_a".to_string(), vec!["lab_a".to_string()])]),
+                    },
+                )]),
+                ..Default::default()
+            }),
+            ..test_context(&config).await
+        });
+        // claims of an authenticated lab client
+        let claims = Claims {
+            sub: "lab_a_client".to_string(),
+            realm_access: Some(Access {
+                roles: vec!["lab_a".to_string()],
+            }),
+            ..Default::default()
+        };
+        let router = build_router(state, None).layer(Extension(claims));
+        let server = TestServer::new(router).unwrap();
+
+        // other lab
+        let response = server.get("/api/pseudonyms/trial/lab/lab_b/psn").await;
+        response.assert_status_forbidden();
+        assert_eq!(response.json::<serde_json::Value>()["code"], "forbidden");
+
+        // whole trial
+        let response = server.get("/api/participants/trial/psn/idat").await;
+        response.assert_status_forbidden();
+
+        // match resolution
+        let response = server.get("/api/matches").await;
+        response.assert_status_forbidden();
+    }
+
+    #[tokio::test]
+    async fn trial_access_test() {
+        use crate::config::{Oidc, Trial, TrialAccess};
+        use crate::ttp::client::tests::LIST_DOMAINS_RESPONSE;
+        use auth::oauth::{Access, Claims};
+        use axum::Extension;
+        use httpmock::prelude::*;
+        use std::collections::HashMap;
+
+        let domain_response = |children: &str| {
+            format!(
+                r#"<soap:Envelope xmlns:soap="http://schemas.xmlsoap.org/soap/envelope/">
+    <soap:Body>
+        <ns2:getDomainResponse xmlns:ns2="http://psn.ttp.ganimed.icmvc.emau.org/">
+            <domain>
+                <name>trial</name>
+                <checkDigitClass>org.emau.icmvc.ganimed.ttp.psn.generator.NoCheckDigits</checkDigitClass>
+                <alphabet>org.emau.icmvc.ganimed.ttp.psn.alphabets.Symbol32</alphabet>
+                {children}
+                <config>
+                    <psnLength>16</psnLength>
+                    <psnsDeletable>true</psnsDeletable>
+                    <multiPsnDomain>false</multiPsnDomain>
+                    <sendNotificationsWeb>true</sendNotificationsWeb>
+                </config>
+            </domain>
+        </ns2:getDomainResponse>
+    </soap:Body>
+</soap:Envelope>"#
+            )
+        };
+
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/gpas/DomainService")
+                .body_includes("ns2:listDomains");
+            then.status(200).body(LIST_DOMAINS_RESPONSE);
+        });
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/gpas/DomainService")
+                .body_includes("<domainName>trial</domainName>");
+            then.status(200).body(domain_response(
+                "<childDomainNames>trial_lab_a</childDomainNames>
+                <childDomainNames>trial_lab_b</childDomainNames>",
+            ));
+        });
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/gpas/DomainService")
+                .body_includes("<domainName>trial_lab_");
+            then.status(200).body(domain_response(""));
+        });
+
+        let mut config = setup_config(server.base_url());
+        config.trials = HashMap::from([("other".to_string(), Trial::default())]);
+        let state = Arc::new(ApiContext {
+            access: AccessRules::new(&Oidc {
+                access: HashMap::from([
+                    (
+                        "trial".to_string(),
+                        TrialAccess {
+                            grants: vec![],
+                            labs: HashMap::from([("lab_a".to_string(), vec!["lab_a".to_string()])]),
+                        },
+                    ),
+                    (
+                        "other".to_string(),
+                        TrialAccess {
+                            grants: vec!["other_manager".to_string()],
+                            labs: HashMap::new(),
+                        },
+                    ),
+                ]),
+                ..Default::default()
+            }),
+            ..test_context(&config).await
+        });
+        // claims of an authenticated lab client
+        let claims = Claims {
+            sub: "lab_a_client".to_string(),
+            realm_access: Some(Access {
+                roles: vec!["lab_a".to_string()],
+            }),
+            ..Default::default()
+        };
+        let router = build_router(state, None).layer(Extension(claims));
+        let server = TestServer::new(router).unwrap();
+
+        // only accessible trials are listed
+        let response = server.get("/api/trials").await;
+        response.assert_status_ok();
+        let trials = response.json::<serde_json::Value>();
+        assert_eq!(trials.as_array().unwrap().len(), 1);
+        assert_eq!(trials[0]["name"], "trial");
+
+        let response = server.get("/api/trials/other").await;
+        response.assert_status_forbidden();
+
+        // only accessible labs are returned
+        let response = server.get("/api/trials/trial").await;
+        response.assert_status_ok();
+        let labs = &response.json::<serde_json::Value>()["labs"];
+        assert_eq!(labs.as_array().unwrap().len(), 1);
+        assert_eq!(labs[0]["name"], "lab_a");
+    }
+
+    #[tokio::test]
+    async fn audit_test() {
+        use crate::audit::Principal;
+        use crate::model::{AuditAction, AuditEvent};
+
+        let config = AppConfig::default();
+        let state = Arc::new(test_context(&config).await);
+        let principal = Principal::from(&None);
+        for trial in ["trial", "other"] {
+            state
+                .audit
+                .record(
+                    &AuditEvent::new(&principal, AuditAction::ReadIdat, Some(trial))
+                        .with_pseudonym("psn"),
+                )
+                .await
+                .unwrap();
+        }
+        let server = TestServer::new(build_router(state.clone(), None)).unwrap();
+
+        let response = server
+            .get("/api/audit")
+            .add_query_param("trial", "trial")
+            .add_query_param("from", "2025-01-01T00:00:00Z")
+            .await;
+
+        response.assert_status_ok();
+        let audit = response.json::<serde_json::Value>();
+        assert_eq!(audit["valid"], true);
+        assert_eq!(audit["head"], json!(state.audit.head().await.unwrap()));
+        assert_eq!(audit["entries"].as_array().unwrap().len(), 1);
+        assert_eq!(audit["entries"][0]["action"], "read_idat");
+        assert_eq!(audit["entries"][0]["principal"], "anonymous");
+    }
+
+    #[tokio::test]
+    async fn create_trial_audit_test() {
+        use httpmock::prelude::*;
+
+        let domain_response = r#"<soap:Envelope xmlns:soap="http://schemas.xmlsoap.org/soap/envelope/">
+    <soap:Body>
+        <ns2:getDomainResponse xmlns:ns2="http://psn.ttp.ganimed.icmvc.emau.org/">
+            <domain>
+                <name>trial</name>
+                <checkDigitClass>org.emau.icmvc.ganimed.ttp.psn.generator.NoCheckDigits</checkDigitClass>
+                <alphabet>org.emau.icmvc.ganimed.ttp.psn.alphabets.Symbol32</alphabet>
+                <config>
+                    <psnLength>16</psnLength>
+                    <psnsDeletable>true</psnsDeletable>
+                    <multiPsnDomain>false</multiPsnDomain>
+                    <sendNotificationsWeb>true</sendNotificationsWeb>
+                </config>
+            </domain>
+        </ns2:getDomainResponse>
+    </soap:Body>
+</soap:Envelope>"#;
+
+        let server = MockServer::start();
+        let add_mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/gpas/DomainService")
+                .body_includes("ns2:addDomain");
+            then.status(200);
+        });
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/gpas/DomainService")
+                .body_includes("ns2:getDomain");
+            then.status(200).body(domain_response);
+        });
+
+        let config = setup_config(server.base_url());
+        let state = Arc::new(test_context(&config).await);
+        let server = TestServer::new(build_router(state.clone(), None)).unwrap();
+
+        let response = server
+            .post("/api/trials")
+            .json(&json!({ "name": "trial", "labs": ["lab"] }))
+            .await;
+
+        response.assert_status(axum::http::StatusCode::CREATED);
+        add_mock.assert_calls(2);
+        let entries = state.audit.query(&Default::default()).await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(
+            entries[0].event.action,
+            crate::model::AuditAction::CreateTrial
+        );
+        assert_eq!(entries[0].event.trial.as_deref(), Some("trial"));
+        assert_eq!(entries[0].event.labs, vec!["lab"]);
+    }
 }
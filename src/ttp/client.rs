@@ -1,8 +1,15 @@
 use crate::api::IdRequest;
-use crate::config::{Epix, Gpas, Ttp};
-use crate::ttp::epix::model::{GetPossibleMatchesForPersonResponseBody, PossibleMatchResult};
-use crate::ttp::gpas::model::{GetDomainResponseBody, GetPseudonymsForResponseBody};
-use crate::ttp::gpas::PsnOperation;
+use crate::config::{self, DomainSettings, Epix, Gpas, Trial, Ttp};
+use crate::model::Idat;
+use crate::ttp::epix::model::{
+    DomainOut, GetPersonByMpiResponseBody, GetPossibleMatchesForDomainResponseBody,
+    GetPossibleMatchesForPersonResponseBody, MatchingConfig, MpiIdentity, PossibleMatchForDomain,
+    Person, PossibleMatchResult, UpdatePersonResponseBody,
+};
+use crate::ttp::gpas::model::{
+    Domain, GetDomainResponseBody, GetPseudonymsForResponseBody, ListDomainsResponseBody,
+};
+use crate::ttp::gpas::{PsnOperation, DOMAIN_COMMENT};
 use crate::ttp::{epix, gpas};
 use anyhow::anyhow;
 use fhir_model::r4b::resources::{Parameters, ParametersParameter, ParametersParameterValue};
@@ -12,15 +19,37 @@ use reqwest::header::{HeaderMap, HeaderValue};
 use reqwest::{header, Client, Error, Response};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt;
 use std::sync::Arc;
 use std::time::Duration;
+use tokio::sync::Semaphore;
 use tokio::task::JoinSet;
 
+/// Maximum of concurrent pseudonym lookups in other gPAS domains
+const DOMAIN_LOOKUP_CONCURRENCY: usize = 8;
+
+/// E-PIX domain and data source used for a request
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct EpixScope {
+    pub(crate) domain: String,
+    pub(crate) source: String,
+}
+
 #[derive(Debug, Clone)]
 pub(crate) struct TtpClient {
     client: Client,
     epix: Epix,
     gpas: Gpas,
+    trials: HashMap<String, Trial>,
+    matching_configs: HashMap<String, DomainMatchingConfig>,
+}
+
+/// Validated matching config of an E-PIX domain
+#[derive(Debug, Clone)]
+struct DomainMatchingConfig {
+    path: String,
+    xml: String,
+    config: MatchingConfig,
 }
 
 impl TtpClient {
@@ -40,41 +69,185 @@ impl TtpClient {
         let soap = epix::id_domain_request(self.epix.identifier_domain.to_string());
         self.create_epix_domain(soap.try_into()?).await?;
 
-        // create data source
-        let soap = epix::data_source_request(self.epix.data_source.to_string());
-        self.create_epix_domain(soap.try_into()?).await?;
+        // create data sources
+        for source in self.epix_data_sources() {
+            let soap = epix::data_source_request(source.to_string());
+            self.create_epix_domain(soap.try_into()?).await?;
+        }
+
+        // epix study domains
+        for domain in std::iter::once(&self.epix.domain).chain(&self.epix.domains) {
+            let soap = epix::create_domain_request(
+                domain.name.to_string(),
+                domain.description.to_string(),
+                self.epix.identifier_domain.to_string(),
+                self.epix.data_source.to_string(),
+                self.matching_config(&domain.name).xml.clone(),
+            );
+
+            let body = soap.try_into()?;
+            self.create_epix_domain(body).await?;
+            self.check_matching_config(domain).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Compare the parsed matching config of an existing E-PIX domain with the configured file
+    async fn check_matching_config(&self, domain: &config::Domain) -> anyhow::Result<()> {
+        let matching = self.matching_config(&domain.name);
+        let live = match self.get_epix_domain(&domain.name).await {
+            Ok(live) => live,
+            Err(e) => {
+                warn!(
+                    "Failed to check matching config of E-PIX domain {}: {e}",
+                    domain.name
+                );
+                return Ok(());
+            }
+        };
+        match epix::parse_matching_config(&live.config) {
+            Ok(config) if config == matching.config => return Ok(()),
+            Ok(_) => {}
+            Err(e) => debug!(
+                "Failed to parse matching config of E-PIX domain {}: {e}",
+                domain.name
+            ),
+        }
+
+        if !self.epix.update_matching_config {
+            warn!(
+                "Matching config of E-PIX domain {} differs from {}",
+                domain.name, matching.path
+            );
+            return Ok(());
+        }
 
-        // epix study domain
-        let soap = epix::create_domain_request(
-            self.epix.domain.name.to_string(),
-            self.epix.domain.description.to_string(),
+        info!(
+            "Updating matching config of E-PIX domain {} from {}",
+            domain.name, matching.path
+        );
+        let body: String = epix::update_domain_request(
+            domain.name.to_string(),
+            domain.description.to_string(),
             self.epix.identifier_domain.to_string(),
             self.epix.data_source.to_string(),
-        )?;
+            matching.xml.clone(),
+        )
+        .try_into()?;
 
-        let body = soap.try_into()?;
-        self.create_epix_domain(body).await?;
+        let response = self.send_epix_management(body).await?;
+        if !response.status().is_success() {
+            let resp_text = response.text().await?;
+            return Err(match FaultEnvelope::try_from(resp_text.clone()) {
+                Ok(fault) => fault.into_error("Failed to update E-PIX domain"),
+                Err(_) => anyhow!("Failed to update E-PIX domain: {resp_text}"),
+            });
+        }
 
         Ok(())
     }
 
-    async fn setup_gpas_domains(
+    async fn get_epix_domain(&self, domain: &str) -> anyhow::Result<DomainOut> {
+        let body: String = epix::domain_request(domain.to_string()).try_into()?;
+
+        let response = self.send_epix(body).await?;
+        let resp_text = response.text().await?;
+        let domain =
+            SoapEnvelope::<epix::model::GetDomainResponseBody>::try_from(resp_text.as_str())
+                .map_err(|_| match FaultEnvelope::try_from(resp_text.clone()) {
+                    Ok(fault) => fault.into_error("Failed to get E-PIX domain"),
+                    Err(_) => anyhow!("Failed to get E-PIX domain: {resp_text}"),
+                })?;
+
+        Ok(domain.body.get_domain_response.returns)
+    }
+
+    fn matching_config(&self, domain: &str) -> &DomainMatchingConfig {
+        &self.matching_configs[domain]
+    }
+
+    /// Names of all configured E-PIX domains
+    pub(crate) fn epix_domains(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(&self.epix.domain)
+            .chain(&self.epix.domains)
+            .map(|d| d.name.as_str())
+    }
+
+    /// All configured E-PIX data sources
+    fn epix_data_sources(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(&self.epix.data_source)
+            .chain(&self.epix.data_sources)
+            .map(String::as_str)
+    }
+
+    fn is_configured_source(&self, source: &str) -> bool {
+        self.epix_data_sources().any(|s| s == source)
+    }
+
+    /// Whether a configured data source may be used by requests of the trial
+    pub(crate) fn is_data_source(&self, trial: &str, source: &str) -> bool {
+        if !self.is_configured_source(source) {
+            return false;
+        }
+
+        match self.trials.get(trial) {
+            Some(t) if !t.sources.is_empty() => {
+                source == self.trial_source(Some(t)) || t.sources.iter().any(|s| s == source)
+            }
+            _ => true,
+        }
+    }
+
+    /// Data source of a trial or the default source
+    fn trial_source<'a>(&'a self, trial: Option<&'a Trial>) -> &'a str {
+        trial
+            .and_then(|t| t.data_source.as_deref())
+            .unwrap_or(&self.epix.data_source)
+    }
+
+    /// E-PIX domain and data source of a trial. The source of a request takes precedence
+    pub(crate) fn epix_scope(&self, trial: &str, source: Option<&str>) -> EpixScope {
+        let trial = self.trials.get(trial);
+
+        EpixScope {
+            domain: trial
+                .and_then(|t| t.epix_domain.clone())
+                .unwrap_or_else(|| self.epix.domain.name.clone()),
+            source: source.unwrap_or(self.trial_source(trial)).to_string(),
+        }
+    }
+
+    /// Create the trial domain and its lab (sub) domains
+    pub(crate) async fn setup_gpas_domains(
         &self,
         study: &str,
-        lab: &HashMap<String, u32>,
+        labs: impl Iterator<Item = &String>,
     ) -> anyhow::Result<()> {
         // gpas
         // primary domain
-        let soap_request = gpas::create_domain_request(study.to_string(), None, None, false, None);
+        let soap_request = gpas::create_domain_request(
+            study.to_string(),
+            &self.domain_settings(study, None),
+            false,
+            None,
+        );
         let body: String = soap_request.try_into()?;
         self.create_gpas_domain(body).await?;
 
         // lab (sub) domains
-        for l in lab.keys() {
+        self.setup_gpas_lab_domains(study, labs).await
+    }
+
+    async fn setup_gpas_lab_domains(
+        &self,
+        study: &str,
+        labs: impl Iterator<Item = &String>,
+    ) -> anyhow::Result<()> {
+        for l in labs {
             let soap_request = gpas::create_domain_request(
                 format!("{study}_{l}"),
-                None,
-                None,
+                &self.domain_settings(study, Some(l)),
                 true,
                 Some(study.to_string()),
             );
@@ -85,6 +258,67 @@ impl TtpClient {
         Ok(())
     }
 
+    /// Pseudonyms of failed requests are rolled back, so new domains must be configured with
+    /// deletable pseudonyms. Existing domains cannot be changed and are only warned about.
+    pub(crate) async fn check_deletable(&self) -> anyhow::Result<()> {
+        let mut settings = Vec::new();
+        if !self.gpas.psns_deletable {
+            settings.push("ttp.gpas.psns_deletable".to_string());
+        }
+        for (name, trial) in &self.trials {
+            let labs = trial.labs.values();
+            if std::iter::once(&trial.domain)
+                .chain(labs)
+                .any(|s| s.psns_deletable == Some(false))
+            {
+                settings.push(format!("trials.{name}"));
+            }
+        }
+        if !settings.is_empty() {
+            settings.sort();
+            return Err(anyhow!(
+                "gPAS pseudonyms must be deletable to roll back failed requests: {}",
+                settings.join(", ")
+            ));
+        }
+
+        let domains = self.list_domains().await?;
+        let trials = domains
+            .iter()
+            .filter(|d| self.is_trial(d))
+            .map(|d| d.name.as_str())
+            .collect::<Vec<_>>();
+        for domain in &domains {
+            let trial = domain.parent_domain_names.as_ref().unwrap_or(&domain.name);
+            if trials.contains(&trial.as_str()) && !domain.config.psns_deletable {
+                warn!(
+                    "gPAS pseudonyms of domain {} are not deletable, new identities of failed requests in trial {trial} keep them",
+                    domain.name
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Domains are only created on the fly if strict mode is disabled
+    pub(crate) fn is_strict(&self) -> bool {
+        self.gpas.strict
+    }
+
+    /// Configured domain settings for a trial or one of its labs
+    fn domain_settings(&self, trial: &str, lab: Option<&str>) -> DomainSettings {
+        let trial = self.trials.get(trial);
+        let mut settings = match lab {
+            Some(l) => trial.and_then(|t| t.labs.get(l)).cloned(),
+            None => trial.map(|t| t.domain.clone()),
+        }
+        .unwrap_or_default();
+
+        settings.psns_deletable = settings.psns_deletable.or(Some(self.gpas.psns_deletable));
+        settings
+    }
+
     async fn create_gpas_domain(&self, body: String) -> Result<(), anyhow::Error> {
         let request = self
             .client
@@ -107,16 +341,7 @@ impl TtpClient {
     }
 
     async fn create_epix_domain(&self, body: String) -> anyhow::Result<()> {
-        let request = self
-            .client
-            .post(format!("{}/epix/epixManagementService?wsdl", self.epix.base_url).as_str())
-            .header(
-                header::CONTENT_TYPE,
-                HeaderValue::from_static("application/soap+xml"),
-            )
-            .body(body);
-
-        let response = request.send().await?;
+        let response = self.send_epix_management(body).await?;
 
         // if no success, check if already created
         if !response.status().is_success() {
@@ -131,47 +356,47 @@ impl TtpClient {
 
     pub(crate) async fn delete_identity(&self, identity_id: u32) -> anyhow::Result<()> {
         // deactivate first
-        let body: String = epix::deactivate_entity_request(identity_id).try_into()?;
-        let response = self.send_epix(body).await?;
+        self.deactivate_identity(identity_id).await?;
 
+        // delete identity
+        let body: String = epix::delete_entity_request(identity_id).try_into()?;
+        let response = self.send_epix(body).await?;
         if !response.status().is_success() {
             let resp_text = response.text().await?;
             let fault = FaultEnvelope::try_from(resp_text.clone())
-                .map_err(|_| anyhow!("Failed to deactivate E-PIX identity: {}", resp_text))?;
+                .map_err(|_| anyhow!("Failed to delete E-PIX identity: {}", resp_text))?;
 
-            return Err(anyhow!(
-                "Failed to deactivate E-PIX identity: {}",
-                fault.body.fault.faultstring
-            ));
+            return Err(fault.into_error("Failed to delete E-PIX identity"));
         }
-        debug!("E-PIX identity with id: {identity_id} successfully deactivated");
 
-        // delete identity
-        let body: String = epix::delete_entity_request(identity_id).try_into()?;
+        debug!("E-PIX identity with id: {identity_id} successfully deleted");
+
+        Ok(())
+    }
+
+    pub(crate) async fn deactivate_identity(&self, identity_id: u32) -> anyhow::Result<()> {
+        let body: String = epix::deactivate_entity_request(identity_id).try_into()?;
         let response = self.send_epix(body).await?;
+
         if !response.status().is_success() {
             let resp_text = response.text().await?;
             let fault = FaultEnvelope::try_from(resp_text.clone())
-                .map_err(|_| anyhow!("Failed to delete E-PIX identity: {}", resp_text))?;
+                .map_err(|_| anyhow!("Failed to deactivate E-PIX identity: {}", resp_text))?;
 
-            return Err(anyhow!(
-                "Failed to delete E-PIX identity: {}",
-                fault.body.fault.faultstring
-            ));
+            return Err(fault.into_error("Failed to deactivate E-PIX identity"));
         }
-
-        debug!("E-PIX identity with id: {identity_id} successfully deleted");
+        debug!("E-PIX identity with id: {identity_id} successfully deactivated");
 
         Ok(())
     }
 
     pub(crate) async fn possible_matches_for_person(
         &self,
+        domain: &str,
         mpi: String,
     ) -> anyhow::Result<Vec<PossibleMatchResult>> {
         let body: String =
-            epix::possible_matches_for_person_request(self.epix.domain.name.clone(), mpi)
-                .try_into()?;
+            epix::possible_matches_for_person_request(domain.to_string(), mpi).try_into()?;
 
         let request = self
             .client
@@ -223,7 +448,139 @@ impl TtpClient {
         Ok(())
     }
 
-    pub(crate) async fn new(config: &Ttp) -> Result<Self, anyhow::Error> {
+    pub(crate) async fn possible_matches_for_domain(
+        &self,
+        domain: &str,
+    ) -> anyhow::Result<Vec<PossibleMatchForDomain>> {
+        let body: String =
+            epix::possible_matches_for_domain_request(domain.to_string()).try_into()?;
+
+        let response = self.send_epix(body).await?;
+        let resp_body = response.text().await?;
+        let matched =
+            SoapEnvelope::<GetPossibleMatchesForDomainResponseBody>::try_from(resp_body.as_str())?;
+
+        Ok(matched
+            .body
+            .get_possible_matches_for_domain_response
+            .returns)
+    }
+
+    pub(crate) async fn assign_identity(
+        &self,
+        link_id: u32,
+        winning_identity_id: u32,
+    ) -> anyhow::Result<()> {
+        let body: String =
+            epix::assign_identity_request(link_id, winning_identity_id).try_into()?;
+
+        let response = self.send_epix(body).await?;
+        if !response.status().is_success() {
+            let resp_text = response.text().await?;
+            let fault = FaultEnvelope::try_from(resp_text.clone())
+                .map_err(|_| anyhow!("Failed to merge E-PIX identities: {resp_text}"))?;
+
+            return Err(fault.into_error("Failed to merge E-PIX identities"));
+        }
+        debug!("E-PIX possible match with link id: {link_id} successfully merged");
+
+        Ok(())
+    }
+
+    pub(crate) async fn get_person(
+        &self,
+        domain: &str,
+        mpi: String,
+    ) -> anyhow::Result<MpiIdentity> {
+        Ok(self
+            .get_person_by_mpi(domain, mpi)
+            .await?
+            .reference_identity)
+    }
+
+    /// Ids of all identities of a person, starting with its reference identity
+    pub(crate) async fn person_identities(
+        &self,
+        domain: &str,
+        mpi: String,
+    ) -> anyhow::Result<Vec<u32>> {
+        let person = self.get_person_by_mpi(domain, mpi).await?;
+
+        Ok(std::iter::once(person.reference_identity.identity_id)
+            .chain(person.other_identities.iter().map(|i| i.identity_id))
+            .collect())
+    }
+
+    async fn get_person_by_mpi(&self, domain: &str, mpi: String) -> anyhow::Result<Person> {
+        let body: String = epix::person_by_mpi_request(domain.to_string(), mpi).try_into()?;
+
+        let response = self.send_epix(body).await?;
+        let resp_text = response.text().await?;
+        let person = SoapEnvelope::<GetPersonByMpiResponseBody>::try_from(resp_text.as_str())
+            .map_err(|_| match FaultEnvelope::try_from(resp_text.clone()) {
+                Ok(fault) => fault.into_error("Failed to get E-PIX person"),
+                Err(_) => anyhow!("Failed to get E-PIX person: {resp_text}"),
+            })?;
+
+        Ok(person.body.get_person_by_mpi_response.returns)
+    }
+
+    /// Update the IDAT of a person and return the stored identity. The address is added as a
+    /// new contact only if it differs from the latest one.
+    pub(crate) async fn update_person(
+        &self,
+        scope: &EpixScope,
+        mpi: String,
+        idat: &Idat,
+    ) -> anyhow::Result<MpiIdentity> {
+        // update identity
+        let body: String = epix::update_person_request(
+            scope.domain.clone(),
+            scope.source.clone(),
+            mpi.clone(),
+            idat,
+        )
+        .try_into()?;
+
+        let response = self.send_epix(body).await?;
+        let resp_text = response.text().await?;
+        let updated = SoapEnvelope::<UpdatePersonResponseBody>::try_from(resp_text.as_str())
+            .map_err(|_| match FaultEnvelope::try_from(resp_text.clone()) {
+                Ok(fault) => fault.into_error("Failed to update E-PIX person"),
+                Err(_) => anyhow!("Failed to update E-PIX person: {resp_text}"),
+            })?;
+        let identity_id = updated
+            .body
+            .update_person_response
+            .returns
+            .person
+            .reference_identity
+            .identity_id;
+        debug!("E-PIX person with mpi: {mpi} successfully updated");
+
+        let person = self.get_person(&scope.domain, mpi.clone()).await?;
+        if Idat::from(person.clone()).same_contact(idat) {
+            return Ok(person);
+        }
+
+        // add current address
+        let body: String = epix::add_contact_request(identity_id, idat).try_into()?;
+        let response = self.send_epix(body).await?;
+        if !response.status().is_success() {
+            let resp_text = response.text().await?;
+            let fault = FaultEnvelope::try_from(resp_text.clone())
+                .map_err(|_| anyhow!("Failed to add E-PIX contact: {resp_text}"))?;
+
+            return Err(fault.into_error("Failed to add E-PIX contact"));
+        }
+
+        self.get_person(&scope.domain, mpi).await
+    }
+
+    pub(crate) async fn new(
+        config: &Ttp,
+        trials: &HashMap<String, Trial>,
+    ) -> Result<Self, anyhow::Error> {
         // default headers
         let mut headers = HeaderMap::new();
         headers.insert(
@@ -237,11 +594,49 @@ impl TtpClient {
             .timeout(Duration::from_secs(config.timeout))
             .build()?;
 
-        Ok(TtpClient {
+        let client = TtpClient {
             client,
             epix: config.epix.clone(),
             gpas: config.gpas.clone(),
-        })
+            trials: trials.clone(),
+            matching_configs: load_matching_configs(&config.epix)?,
+        };
+        client.check_epix_scopes()?;
+
+        Ok(client)
+    }
+
+    /// Trials may only use configured E-PIX domains and data sources
+    fn check_epix_scopes(&self) -> anyhow::Result<()> {
+        for (name, trial) in &self.trials {
+            if let Some(domain) = &trial.epix_domain
+                && !self.epix_domains().any(|d| d == domain)
+            {
+                return Err(anyhow!(
+                    "E-PIX domain {domain} of trial {name} is not configured"
+                ));
+            }
+            if let Some(source) = trial
+                .data_source
+                .iter()
+                .chain(&trial.sources)
+                .find(|s| !self.is_configured_source(s))
+            {
+                return Err(anyhow!(
+                    "E-PIX data source {source} of trial {name} is not configured"
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Required identity fields of the matching config of an E-PIX domain
+    pub(crate) fn required_fields(&self, domain: &str) -> &[String] {
+        self.matching_configs
+            .get(domain)
+            .map(|c| c.config.required_fields.names.as_slice())
+            .unwrap_or_default()
     }
 
     pub(crate) async fn test_connection(&self) -> anyhow::Result<()> {
@@ -279,23 +674,23 @@ impl TtpClient {
         }
     }
 
-    pub(crate) async fn add_person(&self, idat: IdRequest) -> Result<Parameters, anyhow::Error> {
+    pub(crate) async fn add_person(
+        &self,
+        scope: &EpixScope,
+        idat: IdRequest,
+    ) -> Result<Parameters, anyhow::Error> {
         let body = Parameters::builder()
             .parameter(vec![
                 Some(
                     ParametersParameter::builder()
                         .name("domain".to_string())
-                        .value(ParametersParameterValue::String(
-                            self.epix.domain.name.clone(),
-                        ))
+                        .value(ParametersParameterValue::String(scope.domain.clone()))
                         .build()?,
                 ),
                 Some(
                     ParametersParameter::builder()
                         .name("source".to_string())
-                        .value(ParametersParameterValue::String(
-                            self.epix.data_source.clone(),
-                        ))
+                        .value(ParametersParameterValue::String(scope.source.clone()))
                         .build()?,
                 ),
                 Some(
@@ -338,54 +733,71 @@ impl TtpClient {
         Ok(serde_json::from_str(response.text().await?.as_str())?)
     }
 
-    pub(crate) async fn pseudonymize(
+    pub(crate) async fn add_lab_pseudonyms(
         &self,
+        trial: &str,
         mpi: String,
-        id_request: IdRequest,
-    ) -> Result<(String, HashMap<String, Vec<String>>), anyhow::Error> {
-        // create study domains
-        self.setup_gpas_domains(&id_request.trial, &id_request.lab)
-            .await?;
+        lab: &HashMap<String, u32>,
+    ) -> anyhow::Result<HashMap<String, Vec<String>>> {
+        // create missing lab domains
+        if !self.is_strict() {
+            self.setup_gpas_lab_domains(trial, lab.keys()).await?;
+        }
 
-        // pseudonymize mpi
-        let mpi_psn = self
-            .pseudonymize_mpi(id_request.trial.clone(), mpi.clone())
-            .await?;
+        self.pseudonymize_labs(trial, mpi, lab).await
+    }
 
-        // pseudonymize lab ids with mpi value
+    async fn pseudonymize_labs(
+        &self,
+        trial: &str,
+        mpi: String,
+        lab: &HashMap<String, u32>,
+    ) -> anyhow::Result<HashMap<String, Vec<String>>> {
         let mut lab_ids = HashMap::<String, Vec<String>>::new();
-        for (domain, count) in &id_request.lab {
+        for (domain, count) in lab {
             if *count > 0 {
                 let ids = self
-                    .pseudonymize_secondary(
-                        &id_request.trial,
-                        domain,
-                        mpi.clone(),
-                        count.to_string(),
-                    )
+                    .pseudonymize_secondary(trial, domain, mpi.clone(), count.to_string())
                     .await?;
                 lab_ids.insert(domain.clone(), ids);
             }
         }
 
-        Ok((mpi_psn, lab_ids))
+        Ok(lab_ids)
+    }
+
+    pub(crate) async fn pseudonymize_mpi(
+        &self,
+        study: String,
+        mpi: String,
+    ) -> anyhow::Result<String> {
+        self.send_pseudonymize(study, mpi, "$pseudonymizeAllowCreate")
+            .await
+    }
+
+    pub(crate) async fn get_pseudonym(
+        &self,
+        domain: String,
+        value: String,
+    ) -> anyhow::Result<String> {
+        self.send_pseudonymize(domain, value, "$pseudonymize").await
     }
 
-    async fn pseudonymize_mpi(&self, study: String, mpi: String) -> anyhow::Result<String> {
-        let body = gpas::create_psn_request(study, mpi, PsnOperation::Pseudonymize)?;
+    async fn send_pseudonymize(
+        &self,
+        domain: String,
+        value: String,
+        operation: &str,
+    ) -> anyhow::Result<String> {
+        let body = gpas::create_psn_request(domain, value, PsnOperation::Pseudonymize)?;
         let request = self
             .client
-            .post(
-                format!(
-                    "{}/ttp-fhir/fhir/gpas/$pseudonymizeAllowCreate",
-                    self.gpas.base_url
-                )
-                .as_str(),
-            )
+            .post(format!("{}/ttp-fhir/fhir/gpas/{operation}", self.gpas.base_url).as_str())
             .body(serde_json::to_string(&body)?);
 
         let response = request.send().await?;
-        let params = serde_json::from_str(response.text().await?.as_str())?;
+        let status = response.status();
+        let params = gpas::parse_parameters(status, response.text().await?.as_str())?;
         gpas::parse_pseudonym(params, "pseudonym")
     }
 
@@ -397,7 +809,8 @@ impl TtpClient {
             .body(serde_json::to_string(&body)?);
 
         let response = request.send().await?;
-        let params = serde_json::from_str(response.text().await?.as_str())?;
+        let status = response.status();
+        let params = gpas::parse_parameters(status, response.text().await?.as_str())?;
         gpas::parse_pseudonym(params, "original")
     }
 
@@ -450,7 +863,7 @@ impl TtpClient {
         ))
     }
 
-    async fn pseudonymize_secondary(
+    pub(crate) async fn pseudonymize_secondary(
         &self,
         trial: &str,
         lab: &str,
@@ -475,12 +888,24 @@ impl TtpClient {
         Ok(gpas::parse_secondary(params))
     }
 
-    pub(crate) async fn get_secondary_domains(&self, trial: String) -> anyhow::Result<Vec<String>> {
-        // get trial domain
-        let body: String = gpas::create_get_domain_request(trial).try_into()?;
+    pub(crate) async fn delete_pseudonyms(&self, trial: String, mpi: String) -> anyhow::Result<()> {
+        // lab domains first
+        let mut domains = self.get_secondary_domains(trial.clone()).await?;
+        domains.push(trial);
+
+        for domain in domains {
+            self.delete_entry(domain, mpi.clone()).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Delete the pseudonyms of a value in a single gPAS domain
+    pub(crate) async fn delete_entry(&self, domain: String, mpi: String) -> anyhow::Result<()> {
+        let body: String = gpas::delete_entry_request(domain.clone(), mpi).try_into()?;
         let request = self
             .client
-            .post(format!("{}/gpas/DomainService?wsdl", self.gpas.base_url).as_str())
+            .post(format!("{}/gpas/gpasService?wsdl", self.gpas.base_url).as_str())
             .header(
                 header::CONTENT_TYPE,
                 HeaderValue::from_static("application/soap+xml"),
@@ -488,44 +913,259 @@ impl TtpClient {
             .body(body);
 
         let response = request.send().await?;
-        let resp_body = response.text().await?;
-        let matched = SoapEnvelope::<GetDomainResponseBody>::try_from(resp_body.as_str())?;
+        if !response.status().is_success() {
+            let resp_text = response.text().await?;
+            let fault = FaultEnvelope::try_from(resp_text.clone())
+                .map_err(|_| anyhow!("Failed to delete gPAS pseudonyms: {resp_text}"))?;
 
-        Ok(matched
-            .body
-            .get_domain_response
-            .domain
-            .child_domain_names
-            .unwrap_or_default())
+            match fault.body.fault.detail {
+                // no pseudonyms in this domain
+                FaultException::UnknownValue(_) => {
+                    debug!("No pseudonyms to delete in gPAS domain: {domain}")
+                }
+                _ => {
+                    return Err(fault.into_error(&format!(
+                        "Failed to delete gPAS pseudonyms in domain {domain}"
+                    )));
+                }
+            }
+        }
+
+        Ok(())
     }
 
-    async fn send_epix(&self, body: String) -> Result<Response, Error> {
-        let request = self
-            .client
-            .post(format!("{}/epix/epixService?wsdl", self.epix.base_url).as_str())
-            .header(
-                header::CONTENT_TYPE,
-                HeaderValue::from_static("application/soap+xml"),
-            )
-            .body(body);
+    pub(crate) async fn get_secondary_domains(&self, trial: String) -> anyhow::Result<Vec<String>> {
+        let domain = self
+            .get_domain(trial.clone())
+            .await?
+            .ok_or(anyhow!("gPAS domain not found: {trial}"))?;
 
-        request.send().await
+        Ok(domain.child_domain_names.unwrap_or_default())
     }
-}
 
-#[derive(Serialize, Deserialize, PartialEq, Debug)]
-#[serde(rename = "soap:Envelope")]
-pub(crate) struct FaultEnvelope {
-    #[serde(rename = "soap:Body")]
-    pub(crate) body: FaultBody,
-}
+    /// Get a gPAS domain by name or `None` if it does not exist
+    pub(crate) async fn get_domain(&self, name: String) -> anyhow::Result<Option<Domain>> {
+        let body: String = gpas::create_get_domain_request(name).try_into()?;
+        let response = self.send_gpas_domain(body).await?;
 
-#[derive(Serialize, Deserialize, PartialEq, Debug)]
+        let status = response.status();
+        let resp_body = response.text().await?;
+        if !status.is_success() {
+            let fault = FaultEnvelope::try_from(resp_body.clone())
+                .map_err(|_| anyhow!("Failed to get gPAS domain: {resp_body}"))?;
+
+            return match fault.body.fault.detail {
+                FaultException::UnknownDomain(_) => Ok(None),
+                _ => Err(fault.into_error("Failed to get gPAS domain")),
+            };
+        }
+        let matched = SoapEnvelope::<GetDomainResponseBody>::try_from(resp_body.as_str())?;
+
+        Ok(Some(matched.body.get_domain_response.domain))
+    }
+
+    /// Root domains created by this service or configured as trials, e.g. domains which
+    /// predate the `ttp-idm` comment
+    pub(crate) async fn list_trials(&self) -> anyhow::Result<Vec<Domain>> {
+        Ok(self
+            .list_domains()
+            .await?
+            .into_iter()
+            .filter(|d| self.is_trial(d))
+            .collect())
+    }
+
+    fn is_trial(&self, domain: &Domain) -> bool {
+        domain.parent_domain_names.is_none()
+            && (domain.comment.as_deref() == Some(DOMAIN_COMMENT)
+                || self.trials.contains_key(&domain.name))
+    }
+
+    async fn list_domains(&self) -> anyhow::Result<Vec<Domain>> {
+        let body: String = gpas::list_domains_request().try_into()?;
+        let response = self.send_gpas_domain(body).await?;
+
+        let resp_body = response.text().await?;
+        let domains = SoapEnvelope::<ListDomainsResponseBody>::try_from(resp_body.as_str())?;
+
+        Ok(domains.body.list_domains_response.domains)
+    }
+
+    /// gPAS domains outside of a trial and its labs in which a value has pseudonyms. Domains are
+    /// checked concurrently, but at most [`DOMAIN_LOOKUP_CONCURRENCY`] at once.
+    pub(crate) async fn other_pseudonym_domains(
+        &self,
+        trial: &str,
+        value: &str,
+    ) -> anyhow::Result<Vec<String>> {
+        let client = Arc::new(self.clone());
+        let permits = Arc::new(Semaphore::new(DOMAIN_LOOKUP_CONCURRENCY));
+        let mut set = JoinSet::new();
+        for domain in self.list_domains().await? {
+            let in_trial =
+                domain.name == trial || domain.parent_domain_names.as_deref() == Some(trial);
+            if in_trial {
+                continue;
+            }
+
+            let (client, permits, value) =
+                (Arc::clone(&client), Arc::clone(&permits), value.to_string());
+            set.spawn(async move {
+                let _permit = permits.acquire_owned().await?;
+                let found = client.has_pseudonyms(&domain.name, &value).await?;
+                anyhow::Ok(found.then_some(domain.name))
+            });
+        }
+
+        // remaining lookups are aborted on the first error
+        let mut domains = Vec::new();
+        while let Some(res) = set.join_next().await {
+            domains.extend(res??);
+        }
+        domains.sort();
+
+        Ok(domains)
+    }
+
+    async fn has_pseudonyms(&self, domain: &str, value: &str) -> anyhow::Result<bool> {
+        let body: String =
+            gpas::get_secondary_psn_request(domain.to_string(), value.to_string()).try_into()?;
+        let request = self
+            .client
+            .post(format!("{}/gpas/gpasService?wsdl", self.gpas.base_url).as_str())
+            .header(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("application/soap+xml"),
+            )
+            .body(body);
+
+        let resp_body = request.send().await?.text().await?;
+        match SoapEnvelope::<GetPseudonymsForResponseBody>::try_from(resp_body.as_str()) {
+            Ok(pseudonyms) => Ok(!pseudonyms
+                .body
+                .get_pseudonyms_for_response
+                .returns
+                .psn
+                .is_empty()),
+            Err(_) => {
+                let fault = FaultEnvelope::try_from(resp_body.clone())
+                    .map_err(|_| anyhow!("Failed to get gPAS pseudonyms: {resp_body}"))?;
+                match fault.body.fault.detail {
+                    // no pseudonyms in this domain
+                    FaultException::UnknownValue(_) => Ok(false),
+                    _ => Err(fault
+                        .into_error(&format!("Failed to get gPAS pseudonyms in domain {domain}"))),
+                }
+            }
+        }
+    }
+
+    async fn send_gpas_domain(&self, body: String) -> Result<Response, Error> {
+        let request = self
+            .client
+            .post(format!("{}/gpas/DomainService?wsdl", self.gpas.base_url).as_str())
+            .header(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("application/soap+xml"),
+            )
+            .body(body);
+
+        request.send().await
+    }
+
+    async fn send_epix_management(&self, body: String) -> Result<Response, Error> {
+        let request = self
+            .client
+            .post(format!("{}/epix/epixManagementService?wsdl", self.epix.base_url).as_str())
+            .header(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("application/soap+xml"),
+            )
+            .body(body);
+
+        request.send().await
+    }
+
+    async fn send_epix(&self, body: String) -> Result<Response, Error> {
+        let request = self
+            .client
+            .post(format!("{}/epix/epixService?wsdl", self.epix.base_url).as_str())
+            .header(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("application/soap+xml"),
+            )
+            .body(body);
+
+        request.send().await
+    }
+}
+
+/// Read and validate the matching config of each E-PIX domain
+fn load_matching_configs(epix: &Epix) -> anyhow::Result<HashMap<String, DomainMatchingConfig>> {
+    std::iter::once(&epix.domain)
+        .chain(&epix.domains)
+        .map(|d| {
+            let path = d
+                .matching_config
+                .as_deref()
+                .or(epix.matching_config.as_deref())
+                .unwrap_or(epix::DEFAULT_MATCHING_CONFIG);
+            let (xml, config) = epix::load_matching_config(path)?;
+            debug!(
+                "E-PIX domain {} uses matching config {path} ({}, {})",
+                d.name, config.matching_mode, config.mpi_generator
+            );
+
+            Ok((
+                d.name.clone(),
+                DomainMatchingConfig {
+                    path: path.to_string(),
+                    xml,
+                    config,
+                },
+            ))
+        })
+        .collect()
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename = "soap:Envelope")]
+pub(crate) struct FaultEnvelope {
+    #[serde(rename = "soap:Body")]
+    pub(crate) body: FaultBody,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
 pub(crate) struct FaultBody {
     #[serde(rename = "soap:Fault")]
     pub(crate) fault: Fault,
 }
 
+impl FaultEnvelope {
+    pub(crate) fn into_error(self, context: &str) -> anyhow::Error {
+        SoapFault {
+            context: context.to_string(),
+            fault: self.body.fault,
+        }
+        .into()
+    }
+}
+
+/// SOAP fault returned by E-PIX or gPAS
+#[derive(Debug)]
+pub(crate) struct SoapFault {
+    pub(crate) context: String,
+    pub(crate) fault: Fault,
+}
+
+impl fmt::Display for SoapFault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.context, self.fault.faultstring)
+    }
+}
+
+impl std::error::Error for SoapFault {}
+
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
 pub(crate) struct Fault {
     pub(crate) faultcode: String,
@@ -548,6 +1188,10 @@ pub(crate) enum FaultException {
     DuplicateEntry(()),
     #[serde(rename = "ns1:InvalidParameterException")]
     InvalidParameter(InvalidParameterException),
+    #[serde(rename = "ns1:UnknownValueException")]
+    UnknownValue(()),
+    #[serde(rename = "ns1:UnknownDomainException")]
+    UnknownDomain(()),
 }
 
 impl TryFrom<String> for FaultEnvelope {
@@ -602,11 +1246,15 @@ impl<T: serde::Serialize> TryInto<String> for SoapEnvelope<T> {
 
 #[cfg(test)]
 pub(crate) mod tests {
-    use crate::config::{AppConfig, Epix, Gpas, Ttp};
-    use crate::ttp::client::TtpClient;
+    use crate::config::{AppConfig, Domain, DomainSettings, Epix, Gpas, Trial, Ttp};
+    use crate::ttp::client::{EpixScope, TtpClient};
+    use crate::ttp::epix;
     use httpmock::Method::POST;
     use httpmock::MockServer;
     use reqwest::header::CONTENT_TYPE;
+    use serde_json::json;
+    use std::collections::HashMap;
+    use std::fs;
 
     fn init() {
         let _ = env_logger::builder().is_test(true).try_init();
@@ -620,8 +1268,16 @@ pub(crate) mod tests {
                     domain: Default::default(),
                     identifier_domain: Default::default(),
                     data_source: Default::default(),
+                    domains: vec![],
+                    data_sources: vec![],
+                    matching_config: None,
+                    update_matching_config: false,
+                },
+                gpas: Gpas {
+                    base_url,
+                    psns_deletable: false,
+                    strict: false,
                 },
-                gpas: Gpas { base_url },
                 timeout: 5,
             },
             ..Default::default()
@@ -649,7 +1305,7 @@ pub(crate) mod tests {
 
         let config = setup_config(server.base_url());
         // create new client
-        let client = TtpClient::new(&config.ttp).await;
+        let client = TtpClient::new(&config.ttp, &config.trials).await;
 
         // connection test
         let test_result = client.unwrap().test_connection().await;
@@ -676,7 +1332,7 @@ pub(crate) mod tests {
 
         let config = setup_config(server.base_url());
         // create new client
-        let client = TtpClient::new(&config.ttp).await;
+        let client = TtpClient::new(&config.ttp, &config.trials).await;
 
         // connection test
         let test_result = client.unwrap().test_connection().await;
@@ -688,9 +1344,517 @@ pub(crate) mod tests {
         assert!(test_result.is_err());
     }
 
+    #[tokio::test]
+    async fn test_add_lab_pseudonyms() {
+        let secondary_response = json!({
+            "resourceType": "Parameters",
+            "parameter": [{
+                "name": "secondarypseudonym",
+                "part": [
+                    {
+                        "name": "original",
+                        "valueIdentifier": { "value": "1001000000011" }
+                    },
+                    {
+                        "name": "value",
+                        "valueIdentifier": { "value": "K2Q8ZP0W4NXH7C1D" }
+                    }
+                ]
+            }]
+        });
+
+        let server = MockServer::start();
+        let domain_mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/gpas/DomainService")
+                .body_includes("<name>trial_lab</name>");
+            then.status(200);
+        });
+        let secondary_mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/ttp-fhir/fhir/gpas/$pseudonymize-secondary")
+                .body_includes("trial_lab");
+            then.status(200).json_body(secondary_response);
+        });
+
+        let config = setup_config(server.base_url());
+        let client = TtpClient::new(&config.ttp, &config.trials).await.unwrap();
+
+        // act
+        let actual = client
+            .add_lab_pseudonyms(
+                "trial",
+                "1001000000011".to_string(),
+                &HashMap::from([("lab".to_string(), 1)]),
+            )
+            .await
+            .unwrap();
+
+        // lab domain is created and pseudonyms are returned
+        domain_mock.assert();
+        secondary_mock.assert();
+        assert_eq!(
+            actual,
+            HashMap::from([("lab".to_string(), vec!["K2Q8ZP0W4NXH7C1D".to_string()])])
+        );
+    }
+
+    pub(crate) const LIST_DOMAINS_RESPONSE: &str = r#"<soap:Envelope xmlns:soap="http://schemas.xmlsoap.org/soap/envelope/">
+    <soap:Body>
+        <ns2:listDomainsResponse xmlns:ns2="http://psn.ttp.ganimed.icmvc.emau.org/">
+            <return>
+                <name>trial</name>
+                <label>Trial</label>
+                <comment>ttp-idm</comment>
+                <checkDigitClass>org.emau.icmvc.ganimed.ttp.psn.generator.NoCheckDigits</checkDigitClass>
+                <alphabet>org.emau.icmvc.ganimed.ttp.psn.alphabets.Symbol32</alphabet>
+                <childDomainNames>trial_lab</childDomainNames>
+                <config>
+                    <psnLength>16</psnLength>
+                    <psnsDeletable>false</psnsDeletable>
+                    <multiPsnDomain>false</multiPsnDomain>
+                    <sendNotificationsWeb>true</sendNotificationsWeb>
+                </config>
+            </return>
+            <return>
+                <name>trial_lab</name>
+                <comment>ttp-idm</comment>
+                <checkDigitClass>org.emau.icmvc.ganimed.ttp.psn.generator.NoCheckDigits</checkDigitClass>
+                <alphabet>org.emau.icmvc.ganimed.ttp.psn.alphabets.Symbol32</alphabet>
+                <parentDomainNames>trial</parentDomainNames>
+                <config>
+                    <psnLength>16</psnLength>
+                    <psnsDeletable>false</psnsDeletable>
+                    <multiPsnDomain>true</multiPsnDomain>
+                    <sendNotificationsWeb>true</sendNotificationsWeb>
+                </config>
+            </return>
+            <return>
+                <name>other</name>
+                <checkDigitClass>org.emau.icmvc.ganimed.ttp.psn.generator.NoCheckDigits</checkDigitClass>
+                <alphabet>org.emau.icmvc.ganimed.ttp.psn.alphabets.Symbol32</alphabet>
+                <config>
+                    <psnLength>16</psnLength>
+                    <psnsDeletable>false</psnsDeletable>
+                    <multiPsnDomain>false</multiPsnDomain>
+                    <sendNotificationsWeb>true</sendNotificationsWeb>
+                </config>
+            </return>
+        </ns2:listDomainsResponse>
+    </soap:Body>
+</soap:Envelope>"#;
+
+    #[tokio::test]
+    async fn test_list_trials() {
+        let server = MockServer::start();
+        let list_mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/gpas/DomainService")
+                .body_includes("ns2:listDomains");
+            then.status(200).body(LIST_DOMAINS_RESPONSE);
+        });
+
+        let config = setup_config(server.base_url());
+        let client = TtpClient::new(&config.ttp, &config.trials).await.unwrap();
+
+        // act
+        let trials = client.list_trials().await.unwrap();
+
+        // only root domains created by this service
+        list_mock.assert();
+        assert_eq!(
+            trials.iter().map(|d| d.name.as_str()).collect::<Vec<_>>(),
+            vec!["trial"]
+        );
+
+        // and configured trials
+        let mut config = config;
+        config.trials = HashMap::from([
+            ("other".to_string(), Trial::default()),
+            ("trial_lab".to_string(), Trial::default()),
+        ]);
+        let client = TtpClient::new(&config.ttp, &config.trials).await.unwrap();
+        let trials = client.list_trials().await.unwrap();
+        assert_eq!(
+            trials.iter().map(|d| d.name.as_str()).collect::<Vec<_>>(),
+            vec!["trial", "other"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_other_pseudonym_domains() {
+        let pseudonyms_response = r#"<soap:Envelope xmlns:soap="http://schemas.xmlsoap.org/soap/envelope/">
+    <soap:Body>
+        <ns2:getPseudonymsForResponse xmlns:ns2="http://psn.ttp.ganimed.icmvc.emau.org/">
+            <return>
+                <psn>K2Q8ZP0W4NXH7C1D</psn>
+            </return>
+        </ns2:getPseudonymsForResponse>
+    </soap:Body>
+</soap:Envelope>"#;
+
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/gpas/DomainService")
+                .body_includes("ns2:listDomains");
+            then.status(200).body(LIST_DOMAINS_RESPONSE);
+        });
+        let other_mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/gpas/gpasService")
+                .body_includes("<domainName>other</domainName>");
+            then.status(200).body(pseudonyms_response);
+        });
+
+        let config = setup_config(server.base_url());
+        let client = TtpClient::new(&config.ttp, &config.trials).await.unwrap();
+
+        // act
+        let domains = client
+            .other_pseudonym_domains("trial", "1001000000011")
+            .await
+            .unwrap();
+
+        // trial and lab domains are not checked
+        other_mock.assert();
+        assert_eq!(domains, vec!["other"]);
+    }
+
+    #[tokio::test]
+    async fn test_epix_scope() {
+        let mut config = setup_config("http://localhost".to_string());
+        config.ttp.epix.domain.name = "default".to_string();
+        config.ttp.epix.data_source = "default_source".to_string();
+        config.ttp.epix.domains = vec![Domain {
+            name: "project".to_string(),
+            description: "Project domain".to_string(),
+            matching_config: None,
+        }];
+        config.ttp.epix.data_sources = vec!["site".to_string(), "other_site".to_string()];
+        config.trials = HashMap::from([(
+            "trial".to_string(),
+            Trial {
+                epix_domain: Some("project".to_string()),
+                data_source: Some("site".to_string()),
+                ..Default::default()
+            },
+        )]);
+        let client = TtpClient::new(&config.ttp, &config.trials).await.unwrap();
+
+        // trial config
+        assert_eq!(
+            client.epix_scope("trial", None),
+            EpixScope {
+                domain: "project".to_string(),
+                source: "site".to_string(),
+            }
+        );
+        // source of the request takes precedence
+        assert_eq!(
+            client.epix_scope("trial", Some("other_site")).source,
+            "other_site"
+        );
+        // unknown trials use the default domain
+        assert_eq!(
+            client.epix_scope("other", None),
+            EpixScope {
+                domain: "default".to_string(),
+                source: "default_source".to_string(),
+            }
+        );
+        assert!(client.is_data_source("trial", "other_site"));
+        assert!(!client.is_data_source("trial", "unknown"));
+
+        // sources can be restricted per trial
+        config.trials.get_mut("trial").unwrap().sources = vec!["default_source".to_string()];
+        let client = TtpClient::new(&config.ttp, &config.trials).await.unwrap();
+        assert!(client.is_data_source("trial", "site"));
+        assert!(client.is_data_source("trial", "default_source"));
+        assert!(!client.is_data_source("trial", "other_site"));
+        assert!(client.is_data_source("other", "other_site"));
+
+        // trials must use configured domains
+        config.trials.get_mut("trial").unwrap().epix_domain = Some("unknown".to_string());
+        assert!(TtpClient::new(&config.ttp, &config.trials).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_matching_config_drift() {
+        let domain_response = r#"<soap:Envelope xmlns:soap="http://schemas.xmlsoap.org/soap/envelope/">
+    <soap:Body>
+        <ns2:getDomainResponse xmlns:ns2="http://service.epix.ttp.icmvc.emau.org/">
+            <return>
+                <name>test</name>
+                <config>&lt;ns2:MatchingConfiguration/&gt;</config>
+            </return>
+        </ns2:getDomainResponse>
+    </soap:Body>
+</soap:Envelope>"#;
+
+        let server = MockServer::start();
+        let create_mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/epix/epixManagementService")
+                .body_includes("ns1:add");
+            then.status(200);
+        });
+        let mut domain_mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/epix/epixService")
+                .body_includes("<domainName>test</domainName>");
+            then.status(200).body(domain_response);
+        });
+        let update_mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/epix/epixManagementService")
+                .body_includes("ns1:updateDomain")
+                .body_includes("&lt;required-fields&gt;");
+            then.status(200);
+        });
+
+        let mut config = setup_config(server.base_url());
+        config.ttp.epix.domain.name = "test".to_string();
+
+        // drift is only reported by default
+        let client = TtpClient::new(&config.ttp, &config.trials).await.unwrap();
+        client.setup_domains().await.unwrap();
+        create_mock.assert_calls(3);
+        domain_mock.assert();
+        update_mock.assert_calls(0);
+
+        // domain config is updated from the file
+        config.ttp.epix.update_matching_config = true;
+        let client = TtpClient::new(&config.ttp, &config.trials).await.unwrap();
+        client.setup_domains().await.unwrap();
+        update_mock.assert();
+
+        // equal configs are not updated, regardless of formatting
+        domain_mock.delete();
+        let xml = fs::read_to_string(epix::DEFAULT_MATCHING_CONFIG).unwrap();
+        let live = xml
+            .lines()
+            .skip(1)
+            .map(str::trim)
+            .collect::<String>()
+            .replace('<', "&lt;")
+            .replace('>', "&gt;");
+        let mut domain_mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/epix/epixService")
+                .body_includes("<domainName>test</domainName>");
+            then.status(200)
+                .body(domain_response.replace("&lt;ns2:MatchingConfiguration/&gt;", &live));
+        });
+        client.setup_domains().await.unwrap();
+        domain_mock.assert();
+        update_mock.assert_calls(1);
+
+        // failures to get the domain are not fatal
+        domain_mock.delete();
+        let error_mock = server.mock(|when, then| {
+            when.method(POST).path("/epix/epixService");
+            then.status(500);
+        });
+        client.setup_domains().await.unwrap();
+        error_mock.assert();
+        update_mock.assert_calls(1);
+
+        // invalid files are rejected on startup
+        config.ttp.epix.domain.matching_config = Some("Cargo.toml".to_string());
+        assert!(TtpClient::new(&config.ttp, &config.trials).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_check_deletable() {
+        let server = MockServer::start();
+        let list_mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/gpas/DomainService")
+                .body_includes("ns2:listDomains");
+            then.status(200).body(LIST_DOMAINS_RESPONSE);
+        });
+
+        // configured settings must allow rollbacks
+        let mut config = setup_config(server.base_url());
+        let client = TtpClient::new(&config.ttp, &config.trials).await.unwrap();
+        assert!(client.check_deletable().await.is_err());
+
+        config.ttp.gpas.psns_deletable = true;
+        config.trials = HashMap::from([(
+            "trial".to_string(),
+            Trial {
+                labs: HashMap::from([(
+                    "lab".to_string(),
+                    DomainSettings {
+                        psns_deletable: Some(false),
+                        ..Default::default()
+                    },
+                )]),
+                ..Default::default()
+            },
+        )]);
+        let client = TtpClient::new(&config.ttp, &config.trials).await.unwrap();
+        assert_eq!(
+            client.check_deletable().await.unwrap_err().to_string(),
+            "gPAS pseudonyms must be deletable to roll back failed requests: trials.trial"
+        );
+        list_mock.assert_calls(0);
+
+        // existing domains are only warned about
+        config.trials.clear();
+        let client = TtpClient::new(&config.ttp, &config.trials).await.unwrap();
+        assert!(client.check_deletable().await.is_ok());
+        list_mock.assert();
+    }
+
+    #[tokio::test]
+    async fn test_domain_settings() {
+        let lab_settings = DomainSettings {
+            prefix: Some("LAB-".to_string()),
+            alphabet: Some("Numbers".to_string()),
+            check_digit_class: Some("Damm".to_string()),
+            psn_length: Some(10),
+            ..Default::default()
+        };
+        let mut config = setup_config("http://localhost".to_string());
+        config.ttp.gpas.psns_deletable = true;
+        config.trials = HashMap::from([(
+            "trial".to_string(),
+            Trial {
+                domain: DomainSettings {
+                    label: Some("Trial".to_string()),
+                    psns_deletable: Some(false),
+                    ..Default::default()
+                },
+                labs: HashMap::from([("lab".to_string(), lab_settings.clone())]),
+                ..Default::default()
+            },
+        )]);
+        let client = TtpClient::new(&config.ttp, &config.trials).await.unwrap();
+
+        // trial settings override the global default
+        assert_eq!(
+            client.domain_settings("trial", None),
+            DomainSettings {
+                label: Some("Trial".to_string()),
+                psns_deletable: Some(false),
+                ..Default::default()
+            }
+        );
+        // lab settings fall back to the global default
+        assert_eq!(
+            client.domain_settings("trial", Some("lab")),
+            DomainSettings {
+                psns_deletable: Some(true),
+                ..lab_settings
+            }
+        );
+        // unknown trials use defaults only
+        assert_eq!(
+            client.domain_settings("other", None),
+            DomainSettings {
+                psns_deletable: Some(true),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_delete_pseudonyms() {
+        let domain_response = r#"<soap:Envelope xmlns:soap="http://schemas.xmlsoap.org/soap/envelope/">
+    <soap:Body>
+        <ns2:getDomainResponse xmlns:ns2="http://psn.ttp.ganimed.icmvc.emau.org/">
+            <domain>
+                <name>trial</name>
+                <checkDigitClass>org.emau.icmvc.ganimed.ttp.psn.generator.NoCheckDigits</checkDigitClass>
+                <alphabet>org.emau.icmvc.ganimed.ttp.psn.alphabets.Symbol32</alphabet>
+                <childDomainNames>trial_lab1</childDomainNames>
+                <childDomainNames>trial_lab2</childDomainNames>
+                <config>
+                    <psnLength>16</psnLength>
+                    <psnsDeletable>true</psnsDeletable>
+                    <multiPsnDomain>false</multiPsnDomain>
+                    <sendNotificationsWeb>true</sendNotificationsWeb>
+                </config>
+            </domain>
+        </ns2:getDomainResponse>
+    </soap:Body>
+</soap:Envelope>"#;
+        let unknown_value = r#"<soap:Envelope xmlns:soap="http://schemas.xmlsoap.org/soap/envelope/">
+    <soap:Body>
+        <soap:Fault>
+            <faultcode>soap:Server</faultcode>
+            <faultstring>value 1001000000011 not found in domain trial_lab2</faultstring>
+            <detail>
+                <ns1:UnknownValueException xmlns:ns1="http://psn.ttp.ganimed.icmvc.emau.org/"/>
+            </detail>
+        </soap:Fault>
+    </soap:Body>
+</soap:Envelope>"#;
+
+        let server = MockServer::start();
+        let domain_mock = server.mock(|when, then| {
+            when.method(POST).path("/gpas/DomainService");
+            then.status(200).body(domain_response);
+        });
+        let delete_mocks = ["trial", "trial_lab1"].map(|domain| {
+            server.mock(|when, then| {
+                when.method(POST)
+                    .path("/gpas/gpasService")
+                    .body_includes(format!("<domainName>{domain}</domainName>"));
+                then.status(200);
+            })
+        });
+        let unknown_mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/gpas/gpasService")
+                .body_includes("<domainName>trial_lab2</domainName>");
+            then.status(500).body(unknown_value);
+        });
+
+        let config = setup_config(server.base_url());
+        let client = TtpClient::new(&config.ttp, &config.trials).await.unwrap();
+
+        // act
+        let result = client
+            .delete_pseudonyms("trial".to_string(), "1001000000011".to_string())
+            .await;
+
+        // pseudonyms are deleted in all domains, unknown values are ignored
+        domain_mock.assert();
+        delete_mocks.iter().for_each(|m| m.assert());
+        unknown_mock.assert();
+        assert!(result.is_ok());
+    }
+
     #[tokio::test]
     async fn test_get_possible_matches_for_person_response() {
-        let test_response = r#"<soap:Envelope xmlns:soap="http://schemas.xmlsoap.org/soap/envelope/">
+        let server = MockServer::start();
+        let epix_soap_mock = server.mock(|when, then| {
+            when.method(POST).path("/epix/epixService");
+            then.status(200).body(POSSIBLE_MATCHES_RESPONSE);
+        });
+
+        let config = setup_config(server.base_url());
+        // create new client
+        let client = TtpClient::new(&config.ttp, &config.trials).await;
+
+        // check duplicates
+        let test_result = client
+            .unwrap()
+            .possible_matches_for_person("test", "test".to_string())
+            .await;
+
+        // mocks were called once
+        epix_soap_mock.assert();
+
+        // assert client is created and initialized
+        assert!(test_result.is_ok());
+    }
+
+    /// Possible match of the new identity 53 with identity 1
+    pub(crate) const POSSIBLE_MATCHES_RESPONSE: &str = r#"<soap:Envelope xmlns:soap="http://schemas.xmlsoap.org/soap/envelope/">
     <soap:Body>
         <ns2:getPossibleMatchesForPersonResponse xmlns:ns2="http://service.epix.ttp.icmvc.emau.org/">
             <return>
@@ -790,26 +1954,129 @@ pub(crate) mod tests {
     </soap:Body>
 </soap:Envelope>"#;
 
+    const PERSON_RESPONSE: &str = r#"<soap:Envelope xmlns:soap="http://schemas.xmlsoap.org/soap/envelope/">
+    <soap:Body>
+        <ns2:getPersonByMPIResponse xmlns:ns2="http://service.epix.ttp.icmvc.emau.org/">
+            <return>
+                <referenceIdentity>
+                    <birthDate>1972-01-01T00:00:00+01:00</birthDate>
+                    <birthPlace>Musterstadt</birthPlace>
+                    <firstName>Max</firstName>
+                    <lastName>Mustermann</lastName>
+                    <identityId>1</identityId>
+                    <contacts>
+                        <city>Marburg</city>
+                        <zipCode>35037</zipCode>
+                        <contactId>1</contactId>
+                        <identityId>1</identityId>
+                    </contacts>
+                </referenceIdentity>
+                <otherIdentities>
+                    <identityId>7</identityId>
+                </otherIdentities>
+                <otherIdentities>
+                    <identityId>9</identityId>
+                </otherIdentities>
+            </return>
+        </ns2:getPersonByMPIResponse>
+    </soap:Body>
+</soap:Envelope>"#;
+
+    const UPDATE_RESPONSE: &str = r#"<soap:Envelope xmlns:soap="http://schemas.xmlsoap.org/soap/envelope/">
+    <soap:Body>
+        <ns2:updatePersonResponse xmlns:ns2="http://service.epix.ttp.icmvc.emau.org/">
+            <return>
+                <person>
+                    <referenceIdentity>
+                        <identityId>1</identityId>
+                    </referenceIdentity>
+                </person>
+            </return>
+        </ns2:updatePersonResponse>
+    </soap:Body>
+</soap:Envelope>"#;
+
+    #[tokio::test]
+    async fn test_person_identities() {
         let server = MockServer::start();
-        let epix_soap_mock = server.mock(|when, then| {
-            when.method(POST).path("/epix/epixService");
-            then.status(200).body(test_response);
+        let person_mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/epix/epixService")
+                .body_includes("getPersonByMPI");
+            then.status(200).body(PERSON_RESPONSE);
         });
 
         let config = setup_config(server.base_url());
-        // create new client
-        let client = TtpClient::new(&config.ttp).await;
+        let client = TtpClient::new(&config.ttp, &config.trials).await.unwrap();
 
-        // check duplicates
-        let test_result = client
-            .unwrap()
-            .possible_matches_for_person("test".to_string())
-            .await;
+        // act
+        let ids = client
+            .person_identities("test", "1001000000011".to_string())
+            .await
+            .unwrap();
 
-        // mocks were called once
-        epix_soap_mock.assert();
+        // reference identity first
+        person_mock.assert();
+        assert_eq!(ids, vec![1, 7, 9]);
+    }
 
-        // assert client is created and initialized
-        assert!(test_result.is_ok());
+    #[tokio::test]
+    async fn test_update_person() {
+        use crate::model::Idat;
+        use chrono::NaiveDate;
+
+        let server = MockServer::start();
+        let update_mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/epix/epixService")
+                .body_includes("updatePerson");
+            then.status(200).body(UPDATE_RESPONSE);
+        });
+        let person_mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/epix/epixService")
+                .body_includes("getPersonByMPI");
+            then.status(200).body(PERSON_RESPONSE);
+        });
+        let contact_mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/epix/epixService")
+                .body_includes("addContact");
+            then.status(200);
+        });
+
+        let config = setup_config(server.base_url());
+        let client = TtpClient::new(&config.ttp, &config.trials).await.unwrap();
+        let scope = client.epix_scope("trial", None);
+        let idat = Idat {
+            first_name: "Max".to_string(),
+            last_name: "Mustermann".to_string(),
+            birth_date: NaiveDate::from_ymd_opt(1972, 1, 1).unwrap(),
+            birth_place: "Musterstadt".to_string(),
+            postal_code: "35037".to_string(),
+            city: "Marburg".to_string(),
+            ..Default::default()
+        };
+
+        // same address
+        let person = client
+            .update_person(&scope, "1001000000011".to_string(), &idat)
+            .await
+            .unwrap();
+        assert_eq!(Idat::from(person).city, "Marburg");
+        contact_mock.assert_calls(0);
+
+        // relocation
+        let moved = Idat {
+            postal_code: "35039".to_string(),
+            ..idat
+        };
+        client
+            .update_person(&scope, "1001000000011".to_string(), &moved)
+            .await
+            .unwrap();
+        update_mock.assert_calls(2);
+        contact_mock.assert();
+        person_mock.assert_calls(3);
     }
 }
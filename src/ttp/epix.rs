@@ -1,33 +1,75 @@
 pub(crate) mod model;
 
+use crate::model::Idat;
 use crate::ttp::client::SoapEnvelope;
 use crate::ttp::epix::model::{
-    AddDataSource, AddDataSourceBody, AddDomain, AddDomainBody, AddIdentifierDomain,
-    AddIdentifierDomainBody, DataSource, DeactivateIdentityBody, DeleteIdentityBody, Domain,
-    IdentifierDomain, Identity, MpiDomain, PossibleMatchesForPerson, PossibleMatchesForPersonBody,
-    RemovePossibleMatch, RemovePossibleMatchBody, SafeSource,
+    AddContact, AddContactBody, AddDataSource, AddDataSourceBody, AddDomain, AddDomainBody,
+    AddIdentifierDomain, AddIdentifierDomainBody, AssignIdentity, AssignIdentityBody, ContactIn,
+    DataSource, DeactivateIdentityBody, DeleteIdentityBody, Domain, DomainByName, DomainByNameBody,
+    IdentifierDomain, IdentifierDomainName, Identity, IdentityIdentifier, IdentityIn,
+    MatchingConfig, MpiDomain, PersonByMpi, PersonByMpiBody, PossibleMatchesForDomain,
+    PossibleMatchesForDomainBody, PossibleMatchesForPerson, PossibleMatchesForPersonBody,
+    RemovePossibleMatch, RemovePossibleMatchBody, SafeSource, UpdateDomainBody, UpdatePerson,
+    UpdatePersonBody,
 };
-use std::{env, fs};
+use anyhow::anyhow;
+use std::fs;
 use uuid::Uuid;
 
+/// Default matching config file, relative to the working directory
+pub(crate) const DEFAULT_MATCHING_CONFIG: &str = "resources/matching_config.xml";
+
 pub(crate) fn create_domain_request(
     domain: String,
     description: String,
     mpi_domain: String,
     safe_source: String,
-) -> Result<SoapEnvelope<AddDomainBody>, anyhow::Error> {
-    Ok(SoapEnvelope::new(AddDomainBody {
+    config: String,
+) -> SoapEnvelope<AddDomainBody> {
+    SoapEnvelope::new(AddDomainBody {
         add_domain: AddDomain {
-            domain: Domain {
-                name: domain.clone(),
-                description,
-                label: domain,
-                mpi_domain: MpiDomain { name: mpi_domain },
-                safe_source: SafeSource { name: safe_source },
-                config: load_matching_config()?,
-            },
+            domain: epix_domain(domain, description, mpi_domain, safe_source, config),
+        },
+    })
+}
+
+pub(crate) fn update_domain_request(
+    domain: String,
+    description: String,
+    mpi_domain: String,
+    safe_source: String,
+    config: String,
+) -> SoapEnvelope<UpdateDomainBody> {
+    SoapEnvelope::new(UpdateDomainBody {
+        update_domain: AddDomain {
+            domain: epix_domain(domain, description, mpi_domain, safe_source, config),
+        },
+    })
+}
+
+fn epix_domain(
+    domain: String,
+    description: String,
+    mpi_domain: String,
+    safe_source: String,
+    config: String,
+) -> Domain {
+    Domain {
+        name: domain.clone(),
+        description,
+        label: domain,
+        mpi_domain: MpiDomain { name: mpi_domain },
+        safe_source: SafeSource { name: safe_source },
+        config,
+    }
+}
+
+pub(crate) fn domain_request(domain: String) -> SoapEnvelope<DomainByNameBody> {
+    SoapEnvelope::new(DomainByNameBody {
+        get_domain: DomainByName {
+            domain_name: domain,
         },
-    }))
+    })
 }
 
 pub(crate) fn id_domain_request(domain: String) -> SoapEnvelope<AddIdentifierDomainBody> {
@@ -75,6 +117,87 @@ pub(crate) fn possible_matches_for_person_request(
     })
 }
 
+pub(crate) fn possible_matches_for_domain_request(
+    domain: String,
+) -> SoapEnvelope<PossibleMatchesForDomainBody> {
+    SoapEnvelope::new(PossibleMatchesForDomainBody {
+        get_possible_matches_for_domain: PossibleMatchesForDomain {
+            domain_name: domain,
+        },
+    })
+}
+
+pub(crate) fn assign_identity_request(
+    match_id: u32,
+    winning_identity_id: u32,
+) -> SoapEnvelope<AssignIdentityBody> {
+    SoapEnvelope::new(AssignIdentityBody {
+        assign_identity: AssignIdentity {
+            possible_match_id: match_id,
+            winning_identity_id,
+        },
+    })
+}
+
+pub(crate) fn person_by_mpi_request(domain: String, mpi: String) -> SoapEnvelope<PersonByMpiBody> {
+    SoapEnvelope::new(PersonByMpiBody {
+        get_person_by_mpi: PersonByMpi {
+            domain_name: domain,
+            mpi_id: mpi,
+        },
+    })
+}
+
+pub(crate) fn update_person_request(
+    domain: String,
+    source: String,
+    mpi: String,
+    idat: &Idat,
+) -> SoapEnvelope<UpdatePersonBody> {
+    SoapEnvelope::new(UpdatePersonBody {
+        update_person: UpdatePerson {
+            domain_name: domain,
+            source_name: source,
+            mpi_id: mpi,
+            identity: IdentityIn {
+                first_name: idat.first_name.clone(),
+                last_name: idat.last_name.clone(),
+                birth_date: idat.birth_date.format("%Y-%m-%dT00:00:00").to_string(),
+                birth_place: idat.birth_place.clone(),
+                mothers_maiden_name: idat.birth_name.clone(),
+                gender: idat.gender.map(|g| g.epix_code().to_string()),
+                identifiers: idat
+                    .identifiers
+                    .iter()
+                    .map(|i| IdentityIdentifier {
+                        identifier_domain: IdentifierDomainName {
+                            name: i.system.clone(),
+                        },
+                        value: i.value.clone(),
+                    })
+                    .collect(),
+            },
+            force: false,
+            comment: "Updated by ttp-idm".to_string(),
+        },
+    })
+}
+
+pub(crate) fn add_contact_request(identity_id: u32, idat: &Idat) -> SoapEnvelope<AddContactBody> {
+    SoapEnvelope::new(AddContactBody {
+        add_contact: AddContact {
+            identity_id,
+            contact: ContactIn {
+                zip_code: idat.postal_code.clone(),
+                city: idat.city.clone(),
+                street: idat.street.clone(),
+                country_code: idat.country.clone(),
+                phone: idat.phone.clone(),
+            },
+        },
+    })
+}
+
 pub(crate) fn deactivate_entity_request(identity_id: u32) -> SoapEnvelope<DeactivateIdentityBody> {
     SoapEnvelope::new(DeactivateIdentityBody {
         deactivate_identity: Identity { identity_id },
@@ -87,25 +210,35 @@ pub(crate) fn delete_entity_request(identity_id: u32) -> SoapEnvelope<DeleteIden
     })
 }
 
-fn load_matching_config() -> Result<String, anyhow::Error> {
-    // get resource dir
-    let base_dir = env::current_dir()?.join("resources");
+/// Read and validate a matching config file
+pub(crate) fn load_matching_config(path: &str) -> Result<(String, MatchingConfig), anyhow::Error> {
+    let xml = fs::read_to_string(path)
+        .map_err(|e| anyhow!("Failed to read E-PIX matching config {path}: {e}"))?;
+    let config = parse_matching_config(&xml)
+        .map_err(|e| anyhow!("Invalid E-PIX matching config {path}: {e}"))?;
 
-    let matching_config_path = base_dir.join("matching_config.xml");
-    let config = fs::read_to_string(matching_config_path)?;
+    Ok((xml, config))
+}
 
-    Ok(config)
+pub(crate) fn parse_matching_config(xml: &str) -> Result<MatchingConfig, serde_xml_rs::Error> {
+    serde_xml_rs::from_str(xml)
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::model::Idat;
     use crate::ttp::client::FaultException::DuplicateEntry;
     use crate::ttp::client::{Fault, FaultBody, FaultEnvelope, SoapEnvelope};
     use crate::ttp::epix::model::{
+        GetPossibleMatchesForDomainResponse, GetPossibleMatchesForDomainResponseBody,
         GetPossibleMatchesForPersonResponse, GetPossibleMatchesForPersonResponseBody, Identity,
-        IdentityAddress, MatchingIdentity, MpiId, MpiIdentity, PossibleMatchResult,
+        IdentityAddress, MatchingIdentity, MpiId, MpiIdentity, PossibleMatchForDomain,
+        PossibleMatchResult,
     };
-    use chrono::NaiveDate;
+    use crate::ttp::epix::{
+        load_matching_config, parse_matching_config, update_person_request, DEFAULT_MATCHING_CONFIG,
+    };
+    use chrono::{DateTime, NaiveDate};
 
     #[test]
     fn parse_epix_fault_response_test() {
@@ -246,7 +379,12 @@ mod tests {
             get_possible_matches_for_person_response: GetPossibleMatchesForPersonResponse {
                 returns: vec![PossibleMatchResult {
                     link_id: 42,
+                    possible_match_created: DateTime::parse_from_rfc3339(
+                        "2025-11-21T14:16:23.242+01:00",
+                    )
+                    .unwrap(),
                     priority: "OPEN".to_string(),
+                    probability: 3.1481482315455955,
                     matching_identity: MatchingIdentity {
                         identity: MpiIdentity {
                             birth_date: NaiveDate::from_ymd_opt(1972, 1, 1).unwrap(),
@@ -254,10 +392,18 @@ mod tests {
                             first_name: "Erika".to_string(),
                             last_name: "Mustermann".to_string(),
                             mothers_maiden_name: Some("Musterfrau".into()),
-                            contacts: IdentityAddress {
+                            gender: None,
+                            contacts: vec![IdentityAddress {
                                 zip_code: "35037".to_string(),
                                 city: "Marburg".to_string(),
-                            },
+                                contact_id: Some(1),
+                                contact_last_edited: DateTime::parse_from_rfc3339(
+                                    "2025-11-19T23:12:53.361+01:00",
+                                )
+                                .ok(),
+                                ..Default::default()
+                            }],
+                            identifiers: vec![],
                             identity_id: 1,
                         },
                         mpi_id: MpiId {
@@ -272,4 +418,173 @@ mod tests {
         let reverse = SoapEnvelope::try_from(soap).unwrap();
         assert_eq!(matches, reverse);
     }
+
+    #[test]
+    fn test_get_domain_matches_serde() {
+        let soap = r#"<soap:Envelope xmlns:soap="http://schemas.xmlsoap.org/soap/envelope/">
+    <soap:Body>
+        <ns2:getPossibleMatchesForDomainResponse xmlns:ns2="http://service.epix.ttp.icmvc.emau.org/">
+            <return>
+                <linkId>42</linkId>
+                <possibleMatchCreated>2025-11-21T14:16:23.242+01:00</possibleMatchCreated>
+                <priority>OPEN</priority>
+                <probability>3.1481482315455955</probability>
+                <matchingMPIIdentities>
+                    <identity>
+                        <birthDate>1972-01-01T00:00:00+01:00</birthDate>
+                        <birthPlace>Berlin</birthPlace>
+                        <firstName>Erika</firstName>
+                        <lastName>Mustermann</lastName>
+                        <identityId>1</identityId>
+                        <contacts>
+                            <city>Marburg</city>
+                            <zipCode>35037</zipCode>
+                        </contacts>
+                    </identity>
+                    <mpiId>
+                        <value>1001000000001</value>
+                    </mpiId>
+                </matchingMPIIdentities>
+                <matchingMPIIdentities>
+                    <identity>
+                        <birthDate>1972-01-01T00:00:00+01:00</birthDate>
+                        <birthPlace>Berlin</birthPlace>
+                        <firstName>Erika</firstName>
+                        <lastName>Musterfrau</lastName>
+                        <identityId>2</identityId>
+                        <contacts>
+                            <city>Marburg</city>
+                            <zipCode>35037</zipCode>
+                        </contacts>
+                    </identity>
+                    <mpiId>
+                        <value>1001000000002</value>
+                    </mpiId>
+                </matchingMPIIdentities>
+            </return>
+        </ns2:getPossibleMatchesForDomainResponse>
+    </soap:Body>
+</soap:Envelope>"#;
+
+        let identity = |id: u32, last_name: &str, mpi: &str| MatchingIdentity {
+            identity: MpiIdentity {
+                birth_date: NaiveDate::from_ymd_opt(1972, 1, 1).unwrap(),
+                birth_place: "Berlin".to_string(),
+                first_name: "Erika".to_string(),
+                last_name: last_name.to_string(),
+                mothers_maiden_name: None,
+                gender: None,
+                contacts: vec![IdentityAddress {
+                    zip_code: "35037".to_string(),
+                    city: "Marburg".to_string(),
+                    ..Default::default()
+                }],
+                identifiers: vec![],
+                identity_id: id,
+            },
+            mpi_id: MpiId {
+                value: mpi.to_string(),
+            },
+        };
+        let matches = SoapEnvelope::new(GetPossibleMatchesForDomainResponseBody {
+            get_possible_matches_for_domain_response: GetPossibleMatchesForDomainResponse {
+                returns: vec![PossibleMatchForDomain {
+                    link_id: 42,
+                    priority: "OPEN".to_string(),
+                    probability: 3.1481482315455955,
+                    matching_identities: vec![
+                        identity(1, "Mustermann", "1001000000001"),
+                        identity(2, "Musterfrau", "1001000000002"),
+                    ],
+                }],
+            },
+        });
+
+        let reverse = SoapEnvelope::try_from(soap).unwrap();
+        assert_eq!(matches, reverse);
+    }
+
+    #[test]
+    fn update_person_envelope_test() {
+        let soap = r#"
+        <?xml version="1.0" encoding="UTF-8"?>
+        <soap:Envelope xmlns:ns1="http://service.epix.ttp.icmvc.emau.org/" xmlns:ns2="http://psn.ttp.ganimed.icmvc.emau.org/" xmlns:soap="http://schemas.xmlsoap.org/soap/envelope/">
+            <soap:Body>
+                <ns1:updatePerson>
+                    <domainName>test</domainName>
+                    <sourceName>dummy_safe_source</sourceName>
+                    <mpiId>1001000000001</mpiId>
+                    <identity>
+                        <firstName>Erika</firstName>
+                        <lastName>Musterfrau</lastName>
+                        <birthDate>1972-01-01T00:00:00</birthDate>
+                        <birthPlace>Berlin</birthPlace>
+                    </identity>
+                    <force>false</force>
+                    <comment>Updated by ttp-idm</comment>
+                </ns1:updatePerson>
+            </soap:Body>
+        </soap:Envelope>"#.trim();
+
+        let idat = Idat {
+            first_name: "Erika".to_string(),
+            last_name: "Musterfrau".to_string(),
+            birth_date: NaiveDate::from_ymd_opt(1972, 1, 1).unwrap(),
+            birth_place: "Berlin".to_string(),
+            birth_name: None,
+            postal_code: "35037".to_string(),
+            city: "Marburg".to_string(),
+            ..Default::default()
+        };
+
+        let actual: String = update_person_request(
+            "test".to_string(),
+            "dummy_safe_source".to_string(),
+            "1001000000001".to_string(),
+            &idat,
+        )
+        .try_into()
+        .unwrap();
+
+        let expected = soap
+            .split("\n")
+            .map(|s| s.trim())
+            .collect::<Vec<&str>>()
+            .join("");
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn load_matching_config_test() {
+        let (xml, config) = load_matching_config(DEFAULT_MATCHING_CONFIG).unwrap();
+
+        assert_eq!(
+            config.required_fields.names,
+            vec!["firstName", "lastName", "birthDate", "birthPlace"]
+        );
+        assert!(load_matching_config("resources/missing.xml").is_err());
+        assert!(load_matching_config("Cargo.toml").is_err());
+
+        // formatting differences are no drift
+        let live = xml
+            .lines()
+            .skip(1)
+            .map(|l| format!("  {l}"))
+            .collect::<Vec<_>>()
+            .join("\r\n")
+            .replace(
+                "<threshold-possible-match>2.99",
+                "<threshold-possible-match>2.990",
+            );
+        assert_eq!(parse_matching_config(&live).unwrap(), config);
+        assert_ne!(
+            parse_matching_config(&xml.replace("<mpi-prefix>1001", "<mpi-prefix>1002")).unwrap(),
+            config
+        );
+        assert_ne!(
+            parse_matching_config(&xml.replace("<weight>8.0", "<weight>7.0")).unwrap(),
+            config
+        );
+    }
 }
@@ -1,4 +1,4 @@
-use chrono::NaiveDate;
+use chrono::{DateTime, FixedOffset, NaiveDate};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -18,12 +18,36 @@ pub(crate) struct GetPossibleMatchesForPersonResponse {
 #[serde(rename_all = "camelCase")]
 pub(crate) struct PossibleMatchResult {
     pub(crate) link_id: u32,
+    pub(crate) possible_match_created: DateTime<FixedOffset>,
     pub(crate) priority: String,
+    pub(crate) probability: f64,
     #[serde(rename = "matchingMPIIdentity")]
     pub(crate) matching_identity: MatchingIdentity,
     pub(crate) assigned_identity: Identity,
 }
 
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub(crate) struct GetPossibleMatchesForDomainResponseBody {
+    #[serde(rename = "ns2:getPossibleMatchesForDomainResponse")]
+    pub(crate) get_possible_matches_for_domain_response: GetPossibleMatchesForDomainResponse,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub(crate) struct GetPossibleMatchesForDomainResponse {
+    #[serde(rename = "return", default)]
+    pub(crate) returns: Vec<PossibleMatchForDomain>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PossibleMatchForDomain {
+    pub(crate) link_id: u32,
+    pub(crate) priority: String,
+    pub(crate) probability: f64,
+    #[serde(rename = "matchingMPIIdentities")]
+    pub(crate) matching_identities: Vec<MatchingIdentity>,
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct MatchingIdentity {
@@ -45,15 +69,44 @@ pub(crate) struct MpiIdentity {
     pub(crate) birth_place: String,
     pub(crate) first_name: String,
     pub(crate) last_name: String,
-    pub(crate) contacts: IdentityAddress,
+    #[serde(default)]
+    pub(crate) gender: Option<String>,
+    #[serde(default)]
+    pub(crate) contacts: Vec<IdentityAddress>,
+    #[serde(default)]
+    pub(crate) identifiers: Vec<IdentityIdentifier>,
     pub(crate) identity_id: u32,
 }
 
-#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct IdentityAddress {
+    #[serde(default)]
     pub(crate) zip_code: String,
+    #[serde(default)]
     pub(crate) city: String,
+    #[serde(default)]
+    pub(crate) street: Option<String>,
+    #[serde(default)]
+    pub(crate) country_code: Option<String>,
+    #[serde(default)]
+    pub(crate) phone: Option<String>,
+    #[serde(default)]
+    pub(crate) contact_id: Option<u32>,
+    #[serde(default)]
+    pub(crate) contact_last_edited: Option<DateTime<FixedOffset>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct IdentityIdentifier {
+    pub(crate) identifier_domain: IdentifierDomainName,
+    pub(crate) value: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub(crate) struct IdentifierDomainName {
+    pub(crate) name: String,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
@@ -67,6 +120,44 @@ pub(super) struct AddDomain {
     pub(super) domain: Domain,
 }
 
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub(crate) struct UpdateDomainBody {
+    #[serde(rename = "ns1:updateDomain")]
+    pub(super) update_domain: AddDomain,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub(crate) struct DomainByNameBody {
+    #[serde(rename = "ns1:getDomain")]
+    pub(super) get_domain: DomainByName,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct DomainByName {
+    pub(super) domain_name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub(crate) struct GetDomainResponseBody {
+    #[serde(rename = "ns2:getDomainResponse")]
+    pub(crate) get_domain_response: GetDomainResponse,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub(crate) struct GetDomainResponse {
+    #[serde(rename = "return")]
+    pub(crate) returns: DomainOut,
+}
+
+/// E-PIX domain with its current matching config
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub(crate) struct DomainOut {
+    pub(crate) name: String,
+    #[serde(default)]
+    pub(crate) config: String,
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub(super) struct Domain {
@@ -101,6 +192,146 @@ pub(super) struct PossibleMatchesForPerson {
     pub(super) mpi_id: String,
 }
 
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub(crate) struct PossibleMatchesForDomainBody {
+    #[serde(rename = "ns1:getPossibleMatchesForDomain")]
+    pub(super) get_possible_matches_for_domain: PossibleMatchesForDomain,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct PossibleMatchesForDomain {
+    pub(super) domain_name: String,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub(crate) struct AssignIdentityBody {
+    #[serde(rename = "ns1:assignIdentity")]
+    pub(super) assign_identity: AssignIdentity,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct AssignIdentity {
+    pub(super) possible_match_id: u32,
+    pub(super) winning_identity_id: u32,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub(crate) struct UpdatePersonBody {
+    #[serde(rename = "ns1:updatePerson")]
+    pub(super) update_person: UpdatePerson,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct UpdatePerson {
+    pub(super) domain_name: String,
+    pub(super) source_name: String,
+    pub(super) mpi_id: String,
+    pub(super) identity: IdentityIn,
+    pub(super) force: bool,
+    pub(super) comment: String,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct IdentityIn {
+    pub(super) first_name: String,
+    pub(super) last_name: String,
+    pub(super) birth_date: String,
+    pub(super) birth_place: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) mothers_maiden_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) gender: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(super) identifiers: Vec<IdentityIdentifier>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub(crate) struct UpdatePersonResponseBody {
+    #[serde(rename = "ns2:updatePersonResponse")]
+    pub(crate) update_person_response: UpdatePersonResponse,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub(crate) struct UpdatePersonResponse {
+    #[serde(rename = "return")]
+    pub(crate) returns: ResponseEntry,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub(crate) struct ResponseEntry {
+    pub(crate) person: PersonOut,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PersonOut {
+    pub(crate) reference_identity: Identity,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub(crate) struct PersonByMpiBody {
+    #[serde(rename = "ns1:getPersonByMPI")]
+    pub(super) get_person_by_mpi: PersonByMpi,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct PersonByMpi {
+    pub(super) domain_name: String,
+    pub(super) mpi_id: String,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub(crate) struct GetPersonByMpiResponseBody {
+    #[serde(rename = "ns2:getPersonByMPIResponse")]
+    pub(crate) get_person_by_mpi_response: GetPersonByMpiResponse,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub(crate) struct GetPersonByMpiResponse {
+    #[serde(rename = "return")]
+    pub(crate) returns: Person,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Person {
+    pub(crate) reference_identity: MpiIdentity,
+    /// Further identities linked to the person, e.g. by merged matches
+    #[serde(default)]
+    pub(crate) other_identities: Vec<Identity>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub(crate) struct AddContactBody {
+    #[serde(rename = "ns1:addContact")]
+    pub(super) add_contact: AddContact,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct AddContact {
+    pub(super) identity_id: u32,
+    pub(super) contact: ContactIn,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct ContactIn {
+    pub(super) zip_code: String,
+    pub(super) city: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) street: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) country_code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) phone: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
 pub(crate) struct AddIdentifierDomainBody {
     #[serde(rename = "ns1:addIdentifierDomain")]
@@ -169,6 +400,87 @@ pub(crate) struct Identity {
     pub(crate) identity_id: u32,
 }
 
+/// E-PIX matching configuration, limited to the settings which affect the matching results
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct MatchingConfig {
+    pub(crate) matching_mode: String,
+    pub(crate) mpi_generator: String,
+    pub(crate) mpi_prefix: Option<String>,
+    pub(crate) persist_mode: Option<String>,
+    #[serde(default)]
+    pub(crate) required_fields: RequiredFields,
+    #[serde(default)]
+    pub(crate) preprocessing_config: PreprocessingConfig,
+    pub(crate) matching: Option<Matching>,
+}
+
+#[derive(Deserialize, Default, Clone, PartialEq, Debug)]
+pub(crate) struct RequiredFields {
+    #[serde(rename = "name", default)]
+    pub(crate) names: Vec<String>,
+}
+
+#[derive(Deserialize, Default, Clone, PartialEq, Debug)]
+pub(crate) struct PreprocessingConfig {
+    #[serde(rename = "preprocessing-field", default)]
+    pub(crate) fields: Vec<PreprocessingField>,
+}
+
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct PreprocessingField {
+    pub(crate) field_name: String,
+    #[serde(rename = "simple-transformation-type", default)]
+    pub(crate) simple_transformations: Vec<SimpleTransformation>,
+    #[serde(rename = "complex-transformation-type", default)]
+    pub(crate) complex_transformations: Vec<ComplexTransformation>,
+}
+
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct SimpleTransformation {
+    pub(crate) input_pattern: String,
+    #[serde(default)]
+    pub(crate) output_pattern: String,
+}
+
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct ComplexTransformation {
+    pub(crate) qualified_class_name: String,
+}
+
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct Matching {
+    pub(crate) threshold_possible_match: f64,
+    pub(crate) threshold_automatic_match: f64,
+    pub(crate) use_cemfim: Option<bool>,
+    #[serde(rename = "field", default)]
+    pub(crate) fields: Vec<MatchingField>,
+}
+
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct MatchingField {
+    pub(crate) name: String,
+    pub(crate) blocking_threshold: f64,
+    pub(crate) blocking_mode: String,
+    pub(crate) matching_threshold: f64,
+    pub(crate) weight: f64,
+    pub(crate) algorithm: String,
+    pub(crate) multiple_values: Option<MultipleValues>,
+}
+
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct MultipleValues {
+    pub(crate) penalty_not_a_perfect_match: f64,
+    pub(crate) penalty_one_short: f64,
+    pub(crate) penalty_both_short: f64,
+}
+
 mod naive_date_format {
     use chrono::NaiveDate;
     use serde::{self, Deserialize, Deserializer, Serializer};
@@ -1,18 +1,26 @@
 pub(crate) mod model;
 
+use crate::config::DomainSettings;
 use crate::ttp::client::SoapEnvelope;
 pub(crate) use crate::ttp::gpas::model::{
-    AddDomain, AddDomainBody, AddDomainEnvelope, Domain, DomainConfig, GetDomain, GetDomainBody,
-    GetPseudonymsFor, GetPseudonymsForBody, PsnOperation,
+    AddDomain, AddDomainBody, AddDomainEnvelope, DeleteEntry, DeleteEntryBody, Domain,
+    DomainConfig, GetDomain, GetDomainBody, GetPseudonymsFor, GetPseudonymsForBody, ListDomains,
+    ListDomainsBody, PsnOperation,
 };
 use anyhow::anyhow;
+use reqwest::StatusCode;
 use fhir_model::r4b::resources::{Parameters, ParametersParameter, ParametersParameterValue};
 use fhir_model::BuilderError;
+use std::fmt;
+
+/// Marks gPAS domains created by this service
+pub(crate) const DOMAIN_COMMENT: &str = "ttp-idm";
+const ALPHABET_PACKAGE: &str = "org.emau.icmvc.ganimed.ttp.psn.alphabets";
+const CHECK_DIGIT_PACKAGE: &str = "org.emau.icmvc.ganimed.ttp.psn.generator";
 
 pub(crate) fn create_domain_request(
     domain: String,
-    label: Option<String>,
-    prefix: Option<String>,
+    settings: &DomainSettings,
     is_multi_psn: bool,
     parent_domain: Option<String>,
 ) -> AddDomainEnvelope {
@@ -21,16 +29,25 @@ pub(crate) fn create_domain_request(
             add_domain: AddDomain {
                 domain: Domain {
                     name: domain,
-                    label,
-                    check_digit_class: "org.emau.icmvc.ganimed.ttp.psn.generator.NoCheckDigits"
-                        .into(),
-                    alphabet: "org.emau.icmvc.ganimed.ttp.psn.alphabets.Symbol32".into(),
+                    label: settings.label.clone(),
+                    comment: Some(DOMAIN_COMMENT.to_string()),
+                    check_digit_class: qualified_class(
+                        CHECK_DIGIT_PACKAGE,
+                        settings
+                            .check_digit_class
+                            .as_deref()
+                            .unwrap_or("NoCheckDigits"),
+                    ),
+                    alphabet: qualified_class(
+                        ALPHABET_PACKAGE,
+                        settings.alphabet.as_deref().unwrap_or("Symbol32"),
+                    ),
                     parent_domain_names: parent_domain,
                     child_domain_names: None,
                     config: DomainConfig {
-                        psn_length: 16,
-                        psn_prefix: prefix,
-                        psns_deletable: false,
+                        psn_length: settings.psn_length.unwrap_or(16),
+                        psn_prefix: settings.prefix.clone(),
+                        psns_deletable: settings.psns_deletable.unwrap_or(false),
                         multi_psn_domain: is_multi_psn,
                         send_notifications_web: true,
                     },
@@ -40,12 +57,27 @@ pub(crate) fn create_domain_request(
     }
 }
 
+/// Prefix simple class names (e.g. `Damm`) with the gPAS package
+fn qualified_class(package: &str, class: &str) -> String {
+    if class.contains('.') {
+        class.to_string()
+    } else {
+        format!("{package}.{class}")
+    }
+}
+
 pub(crate) fn create_get_domain_request(trial: String) -> SoapEnvelope<GetDomainBody> {
     SoapEnvelope::new(GetDomainBody {
         get_domain: GetDomain { domain_name: trial },
     })
 }
 
+pub(crate) fn list_domains_request() -> SoapEnvelope<ListDomainsBody> {
+    SoapEnvelope::new(ListDomainsBody {
+        list_domains: ListDomains {},
+    })
+}
+
 pub(crate) fn create_psn_request(
     domain: String,
     value: String,
@@ -81,6 +113,15 @@ pub(crate) fn get_secondary_psn_request(
     })
 }
 
+pub(crate) fn delete_entry_request(domain: String, value: String) -> SoapEnvelope<DeleteEntryBody> {
+    SoapEnvelope::new(DeleteEntryBody {
+        delete_entry: DeleteEntry {
+            value,
+            domain_name: domain,
+        },
+    })
+}
+
 pub(crate) fn create_secondary_psn_request(
     domain: String,
     value: String,
@@ -115,6 +156,62 @@ pub(crate) fn create_secondary_psn_request(
         .build()
 }
 
+/// Error reported by a gPAS FHIR operation
+#[derive(Debug, PartialEq)]
+pub(crate) enum OperationError {
+    UnknownValue(String),
+    UnknownDomain(String),
+    Other(String),
+}
+
+impl OperationError {
+    /// Classify an error by its code and message, gPAS reports unknown values and domains as
+    /// "not found" errors
+    fn new(code: Option<&str>, message: String) -> Self {
+        let text = format!("{} {message}", code.unwrap_or_default()).to_lowercase();
+        let not_found = ["not found", "not-found", "unknown", "not exist", "no-exist"]
+            .iter()
+            .any(|t| text.contains(t));
+        match (not_found, text.contains("domain")) {
+            (true, true) => OperationError::UnknownDomain(message),
+            (true, false) => OperationError::UnknownValue(message),
+            _ => OperationError::Other(message),
+        }
+    }
+}
+
+impl fmt::Display for OperationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OperationError::UnknownValue(m)
+            | OperationError::UnknownDomain(m)
+            | OperationError::Other(m) => write!(f, "gPAS operation failed: {m}"),
+        }
+    }
+}
+
+impl std::error::Error for OperationError {}
+
+/// Parameters of a gPAS FHIR response, failed requests are reported as [`OperationError`]
+pub(crate) fn parse_parameters(status: StatusCode, body: &str) -> anyhow::Result<Parameters> {
+    if status.is_success() {
+        return Ok(serde_json::from_str(body)?);
+    }
+
+    // operation outcome of a rejected request
+    let message = serde_json::from_str::<serde_json::Value>(body)
+        .ok()
+        .and_then(|o| o["issue"][0]["diagnostics"].as_str().map(str::to_string))
+        .unwrap_or_else(|| format!("gPAS request failed with status {status}"));
+    Err(match OperationError::new(None, message) {
+        OperationError::Other(m) if status == StatusCode::NOT_FOUND => {
+            OperationError::UnknownValue(m)
+        }
+        e => e,
+    }
+    .into())
+}
+
 pub(crate) fn parse_pseudonym(params: Parameters, target: &str) -> anyhow::Result<String> {
     params
         .parameter
@@ -137,12 +234,13 @@ pub(crate) fn parse_pseudonym(params: Parameters, target: &str) -> anyhow::Resul
             _ => None,
         })
         .next()
-        .ok_or(anyhow!(parse_error(params).unwrap_or(
-            "Failed to parse pseudonym from gPAS response".into()
-        )))
+        .ok_or_else(|| match parse_error(&params) {
+            Some(e) => e.into(),
+            None => anyhow!("Failed to parse pseudonym from gPAS response"),
+        })
 }
 
-fn parse_error(params: Parameters) -> Option<String> {
+fn parse_error(params: &Parameters) -> Option<OperationError> {
     params
         .parameter
         .iter()
@@ -160,7 +258,10 @@ fn parse_error(params: Parameters) -> Option<String> {
             _ => None,
         })
         .filter_map(|p| match &p {
-            Some(ParametersParameterValue::Coding(v)) => v.display.clone(),
+            Some(ParametersParameterValue::Coding(v)) => Some(OperationError::new(
+                v.code.as_deref(),
+                v.display.clone().or(v.code.clone()).unwrap_or_default(),
+            )),
             _ => None,
         })
         .next()
@@ -192,11 +293,13 @@ pub(crate) fn parse_secondary(params: Parameters) -> Vec<String> {
 
 #[cfg(test)]
 mod tests {
+    use crate::config::DomainSettings;
     use crate::ttp::client::FaultException::DomainInUse;
     use crate::ttp::client::{Fault, FaultBody, FaultEnvelope};
-    use crate::ttp::gpas::{create_domain_request, parse_error};
+    use crate::ttp::gpas::{create_domain_request, parse_error, parse_parameters, OperationError};
     use fhir_model::r4b::resources::{Parameters, ParametersParameter, ParametersParameterValue};
     use fhir_model::r4b::types::Coding;
+    use reqwest::StatusCode;
 
     #[test]
     fn add_domain_envelope_test() {
@@ -208,6 +311,7 @@ mod tests {
                     <domainDTO>
                         <name>test</name>
                         <label>label</label>
+                        <comment>ttp-idm</comment>
                         <checkDigitClass>org.emau.icmvc.ganimed.ttp.psn.generator.NoCheckDigits</checkDigitClass>
                         <alphabet>org.emau.icmvc.ganimed.ttp.psn.alphabets.Symbol32</alphabet>
                         <parentDomainNames>parent</parentDomainNames>
@@ -225,8 +329,11 @@ mod tests {
 
         let add_domain = create_domain_request(
             "test".to_string(),
-            Some("label".to_string()),
-            Some("PSN".to_string()),
+            &DomainSettings {
+                label: Some("label".to_string()),
+                prefix: Some("PSN".to_string()),
+                ..Default::default()
+            },
             true,
             Some("parent".to_string()),
         );
@@ -299,8 +406,35 @@ mod tests {
             .unwrap();
 
         // act
-        let err = parse_error(params);
+        let err = parse_error(&params);
+
+        assert_eq!(Some(OperationError::UnknownValue("Not Found".into())), err);
+    }
 
-        assert_eq!(Some("Not Found".into()), err);
+    #[test]
+    fn parse_parameters_test() {
+        let outcome = r#"{
+            "resourceType": "OperationOutcome",
+            "issue": [{ "severity": "error", "code": "processing", "diagnostics": "unknown domain: trial" }]
+        }"#;
+
+        // rejected requests are classified
+        let err = parse_parameters(StatusCode::UNPROCESSABLE_ENTITY, outcome).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<OperationError>(),
+            Some(&OperationError::UnknownDomain(
+                "unknown domain: trial".to_string()
+            ))
+        );
+        let err = parse_parameters(StatusCode::NOT_FOUND, "").unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<OperationError>(),
+            Some(OperationError::UnknownValue(_))
+        ));
+        let err = parse_parameters(StatusCode::INTERNAL_SERVER_ERROR, "").unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<OperationError>(),
+            Some(OperationError::Other(_))
+        ));
     }
 }
@@ -24,6 +24,8 @@ pub(crate) struct AddDomain {
 pub(crate) struct Domain {
     pub(crate) name: String,
     pub(crate) label: Option<String>,
+    #[serde(default)]
+    pub(crate) comment: Option<String>,
     pub(crate) check_digit_class: String,
     pub(crate) alphabet: String,
     pub(crate) parent_domain_names: Option<String>,
@@ -64,6 +66,27 @@ pub(crate) struct GetDomainResponse {
     pub(crate) domain: Domain,
 }
 
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub(crate) struct ListDomainsBody {
+    #[serde(rename = "ns2:listDomains")]
+    pub(crate) list_domains: ListDomains,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub(crate) struct ListDomains {}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub(crate) struct ListDomainsResponseBody {
+    #[serde(rename = "ns2:listDomainsResponse")]
+    pub(crate) list_domains_response: ListDomainsResponse,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub(crate) struct ListDomainsResponse {
+    #[serde(rename = "return", default)]
+    pub(crate) domains: Vec<Domain>,
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
 pub(crate) struct GetPseudonymsForBody {
     #[serde(rename = "ns2:getPseudonymsFor")]
@@ -77,6 +100,19 @@ pub(crate) struct GetPseudonymsFor {
     pub(crate) domain_name: String,
 }
 
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub(crate) struct DeleteEntryBody {
+    #[serde(rename = "ns2:deleteEntry")]
+    pub(crate) delete_entry: DeleteEntry,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DeleteEntry {
+    pub(crate) value: String,
+    pub(crate) domain_name: String,
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
 pub(crate) struct GetPseudonymsForResponseBody {
     #[serde(rename = "ns2:getPseudonymsForResponse")]
@@ -91,6 +127,7 @@ pub(crate) struct GetPseudonymsForResponse {
 
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
 pub(crate) struct GetPseudonymsForResponseReturn {
+    #[serde(default)]
     pub(crate) psn: Vec<String>,
 }
 
@@ -0,0 +1,246 @@
+use crate::error::{ApiError, FieldError};
+use crate::model::{Idat, Identifier};
+use chrono::{Local, Months};
+use unicode_normalization::UnicodeNormalization;
+
+/// Maximum age of a participant in years
+const MAX_AGE: u32 = 150;
+
+/// Trim, compose (NFC) and collapse whitespace of a value
+fn normalize_value(value: &str) -> String {
+    value
+        .nfc()
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Normalize an optional value, empty values are removed
+fn normalize_option(value: Option<String>) -> Option<String> {
+    value.map(|v| normalize_value(&v)).filter(|v| !v.is_empty())
+}
+
+/// Normalize IDAT before it is sent to E-PIX
+pub(crate) fn normalize(idat: Idat) -> Idat {
+    Idat {
+        first_name: normalize_value(&idat.first_name),
+        last_name: normalize_value(&idat.last_name),
+        birth_date: idat.birth_date,
+        birth_place: normalize_value(&idat.birth_place),
+        birth_name: normalize_option(idat.birth_name),
+        gender: idat.gender,
+        postal_code: normalize_value(&idat.postal_code),
+        city: normalize_value(&idat.city),
+        street: normalize_option(idat.street),
+        country: normalize_option(idat.country).map(|c| c.to_uppercase()),
+        phone: normalize_option(idat.phone),
+        identifiers: idat
+            .identifiers
+            .into_iter()
+            .map(|i| Identifier {
+                system: normalize_value(&i.system),
+                value: normalize_value(&i.value),
+            })
+            .collect(),
+    }
+}
+
+/// IDAT field by its E-PIX name
+fn field<'a>(idat: &'a Idat, name: &str) -> Option<(&'static str, Option<&'a str>)> {
+    match name {
+        "firstName" => Some(("first_name", Some(&idat.first_name))),
+        "lastName" => Some(("last_name", Some(&idat.last_name))),
+        "birthPlace" => Some(("birth_place", Some(&idat.birth_place))),
+        "mothersMaidenName" => Some(("birth_name", idat.birth_name.as_deref())),
+        "gender" => Some(("gender", idat.gender.map(|g| g.epix_code()))),
+        _ => None,
+    }
+}
+
+/// Validate normalized IDAT against the required fields of the matching config
+pub(crate) fn validate(idat: &Idat, required_fields: &[String]) -> Result<(), ApiError> {
+    let mut errors = Vec::new();
+    let mut error = |field: &str, message: &str| {
+        errors.push(FieldError {
+            field: field.to_string(),
+            message: message.to_string(),
+        })
+    };
+
+    // required fields
+    for (name, value) in required_fields.iter().filter_map(|f| field(idat, f)) {
+        if value.is_none_or(str::is_empty) {
+            error(name, "must not be empty");
+        }
+    }
+
+    // birth date range
+    let today = Local::now().date_naive();
+    if idat.birth_date > today {
+        error("birth_date", "must not be in the future");
+    } else if today
+        .checked_sub_months(Months::new(MAX_AGE * 12))
+        .is_some_and(|min| idat.birth_date < min)
+    {
+        error(
+            "birth_date",
+            &format!("must not be more than {MAX_AGE} years ago"),
+        );
+    }
+
+    // country code
+    let country = idat.country.as_deref();
+    if country.is_some_and(|c| c.len() != 2 || !c.chars().all(|c| c.is_ascii_alphabetic())) {
+        error("country", "must be an ISO 3166-1 alpha-2 code");
+    }
+
+    // german postal code
+    if country.is_none_or(|c| c == "DE")
+        && !idat.postal_code.is_empty()
+        && (idat.postal_code.len() != 5 || !idat.postal_code.chars().all(|c| c.is_ascii_digit()))
+    {
+        error("postal_code", "must consist of five digits");
+    }
+
+    // phone number
+    if idat.phone.as_deref().is_some_and(|p| {
+        !p.chars()
+            .all(|c| c.is_ascii_digit() || " +-/()".contains(c))
+    }) {
+        error("phone", "must only contain digits, spaces and +-/()");
+    }
+
+    // identifiers
+    for (i, identifier) in idat.identifiers.iter().enumerate() {
+        if identifier.system.is_empty() {
+            error(&format!("identifiers[{i}].system"), "must not be empty");
+        }
+        if identifier.value.is_empty() {
+            error(&format!("identifiers[{i}].value"), "must not be empty");
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ApiError::InvalidIdat(errors))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::{ApiError, FieldError};
+    use crate::model::{Idat, Identifier};
+    use crate::validation::{normalize, validate};
+    use chrono::{Days, Local, NaiveDate};
+
+    fn required_fields() -> Vec<String> {
+        vec![
+            "firstName".to_string(),
+            "lastName".to_string(),
+            "birthDate".to_string(),
+            "birthPlace".to_string(),
+        ]
+    }
+
+    #[test]
+    fn test_normalize() {
+        let idat = Idat {
+            first_name: "  Erika \t Maria ".to_string(),
+            // decomposed umlaut
+            last_name: "Mu\u{0308}ller".to_string(),
+            birth_date: NaiveDate::from_ymd_opt(1972, 1, 1).unwrap(),
+            birth_place: "Bad  Homburg".to_string(),
+            birth_name: Some(" ".to_string()),
+            postal_code: " 35037".to_string(),
+            city: "Marburg ".to_string(),
+            ..Default::default()
+        };
+
+        let actual = normalize(idat);
+
+        assert_eq!(actual.first_name, "Erika Maria");
+        assert_eq!(actual.last_name, "M\u{00FC}ller");
+        assert_eq!(actual.birth_place, "Bad Homburg");
+        assert_eq!(actual.birth_name, None);
+        assert_eq!(actual.postal_code, "35037");
+        assert_eq!(actual.city, "Marburg");
+    }
+
+    #[test]
+    fn test_validate() {
+        let valid = Idat {
+            first_name: "Erika".to_string(),
+            last_name: "Mustermann".to_string(),
+            birth_date: NaiveDate::from_ymd_opt(1972, 1, 1).unwrap(),
+            birth_place: "Berlin".to_string(),
+            birth_name: None,
+            postal_code: "35037".to_string(),
+            city: "Marburg".to_string(),
+            ..Default::default()
+        };
+        assert!(validate(&valid, &required_fields()).is_ok());
+
+        let invalid = Idat {
+            first_name: "".to_string(),
+            birth_date: Local::now().date_naive() + Days::new(1),
+            postal_code: "D-35037".to_string(),
+            ..valid
+        };
+
+        let Err(ApiError::InvalidIdat(errors)) = validate(&invalid, &required_fields()) else {
+            panic!("IDAT should be invalid");
+        };
+        assert_eq!(
+            errors,
+            vec![
+                FieldError {
+                    field: "first_name".to_string(),
+                    message: "must not be empty".to_string(),
+                },
+                FieldError {
+                    field: "birth_date".to_string(),
+                    message: "must not be in the future".to_string(),
+                },
+                FieldError {
+                    field: "postal_code".to_string(),
+                    message: "must consist of five digits".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_address() {
+        let foreign = Idat {
+            first_name: "Erika".to_string(),
+            last_name: "Mustermann".to_string(),
+            birth_date: NaiveDate::from_ymd_opt(1972, 1, 1).unwrap(),
+            birth_place: "Wien".to_string(),
+            postal_code: "1010".to_string(),
+            city: "Wien".to_string(),
+            country: Some("AT".to_string()),
+            ..Default::default()
+        };
+        assert!(validate(&foreign, &required_fields()).is_ok());
+
+        let invalid = Idat {
+            country: Some("Deutschland".to_string()),
+            phone: Some("call me".to_string()),
+            identifiers: vec![Identifier {
+                system: "kvnr".to_string(),
+                value: "".to_string(),
+            }],
+            ..foreign
+        };
+
+        let Err(ApiError::InvalidIdat(errors)) = validate(&invalid, &required_fields()) else {
+            panic!("IDAT should be invalid");
+        };
+        assert_eq!(
+            errors.iter().map(|e| e.field.as_str()).collect::<Vec<_>>(),
+            vec!["country", "phone", "identifiers[0].value"]
+        );
+    }
+}